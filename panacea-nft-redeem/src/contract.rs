@@ -1,13 +1,24 @@
 use std::borrow::Borrow;
+use std::convert::TryInto;
 
-use cosmwasm_std::{attr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, entry_point, Env, from_binary, from_slice, MessageInfo, Response, StdResult, to_binary, WasmMsg};
+use cosmwasm_std::{attr, Attribute, BankMsg, Binary, Coin, ContractResult, CosmosMsg, Decimal, Deps, DepsMut, Empty, entry_point, Env, from_binary, from_slice, MessageInfo, Order, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg, to_binary, to_vec, Uint128, WasmMsg};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
 use cw721::{AllNftInfoResponse, Cw721ReceiveMsg, OwnerOfResponse};
+use cw_storage_plus::{Bound, U64Key};
+use sha2::{Digest, Sha256};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg};
-use crate::query::{ContractInfoResponse, QueryMsg};
-use crate::state::{CONTRACT_INFO, TOKEN_OWNER_INFO};
-use crate::types::{TokenInfo, TokenOwnerInfo};
+use crate::msg::{ExchangeRateResponse, ExecuteMsg, FaceValueSupplyResponse, InstantiateMsg, MintPolicyResponse, OracleQueryMsg, ReceiptExecuteMsg, ReceiptMintMsg, ReceiveMsg, SourceQueryMsg};
+use crate::query::{BudgetResponse, ClaimsResponse, ContractInfoResponse, ContributionInfo, ConversionResponse, DepositorInfo, DepositorsResponse, FeeConfigResponse, IssuerInfo, IssuersResponse, LimitsResponse, PriceCeilingsResponse, QueryMsg, RedeemWindowResponse, StatusResponse, ReservesResponse, UnallocatedResponse};
+use crate::state::{BLOCK_PAYOUT, BUDGETS, CIRCUIT_BREAKER, CLAIM_QUEUES, CLAIM_SEQ, CLAIMS, CONTRACT_INFO, CONTRIBUTIONS, CONVERSIONS, CW20_TOKENS, DEPOSITORS, FEE_CONFIG, ISSUERS, ORACLE, OUTSTANDING, PAUSED, PAYOUT_SLOTS, PRICE_CEILINGS, RATE_LIMITS, RATE_USAGE, REDEEM_WINDOWS, REDEEMED, REFUNDABLE_AT, SENDER_CLAIMS, TOKEN_OWNER_INFO, TOTAL_SHARES, UNALLOCATED, USED_NONCES};
+use crate::types::{Asset, BlockPayout, CircuitBreaker, Claim, Contribution, Conversion, FeeConfig, LegacyTokenInfo, PauseInfo, RateLimits, RateUsage, Receipt, RedeemWindow, TokenInfo, TokenOwnerInfo, Voucher};
+
+const DEFAULT_WIND_DOWN_GRACE_SECONDS: u64 = 30 * 24 * 60 * 60;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const CIRCUIT_BREAKER_SLOTS: u64 = 10;
+const CW20_REFUND_REPLY_ID: u64 = 1;
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -19,7 +30,15 @@ pub fn instantiate(
     let contract_info = ContractInfoResponse {
         source_contracts: msg.source_contracts,
         payer: info.sender.to_string(),
+        wind_down_grace_seconds: msg.wind_down_grace_seconds.unwrap_or(DEFAULT_WIND_DOWN_GRACE_SECONDS),
+        iou_enabled: msg.iou_enabled.unwrap_or(false),
+        redeem_start: msg.redeem_start,
+        redeem_end: msg.redeem_end,
+        receipt_contract: msg.receipt_contract,
+        accepted_denoms: msg.accepted_denoms,
     };
+    validate_receipt_contract(&contract_info, contract_info.receipt_contract.as_ref())?;
+    validate_redeem_window(&RedeemWindow { redeem_start: msg.redeem_start, redeem_end: msg.redeem_end })?;
 
     let res = CONTRACT_INFO.save(deps.storage, &contract_info);
     if res.is_err() {
@@ -37,35 +56,153 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Deposit {} => execute_deposit(deps, env, info),
+        ExecuteMsg::Deposit { source_contract } => execute_deposit(deps, env, info, source_contract),
+        ExecuteMsg::Allocate { source_contract, amount } => execute_allocate(deps, env, info, source_contract, amount),
         ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, env, info, msg),
+        ExecuteMsg::RedeemBatch { contract, token_ids } => execute_redeem_batch(deps, env, info, contract, token_ids),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
         ExecuteMsg::RecoverOwner { contract, token_id } => execute_recover_owner(deps, env, info, contract, token_id),
         ExecuteMsg::Refund {} => execute_refund(deps, env, info),
+        ExecuteMsg::Withdraw {} => execute_withdraw(deps, env, info),
+        ExecuteMsg::WindDown {} => execute_wind_down(deps, env, info),
+        ExecuteMsg::CancelWindDown {} => execute_cancel_wind_down(deps, env, info),
+        ExecuteMsg::ClaimOutstanding {} => execute_claim_outstanding(deps, env, info),
+        ExecuteMsg::SetConversion { from_denom, to_denom, rate, min_rate, max_rate } => {
+            execute_set_conversion(deps, env, info, Conversion { from_denom, to_denom, rate, min_rate, max_rate })
+        }
+        ExecuteMsg::RemoveConversion { from_denom } => execute_remove_conversion(deps, env, info, from_denom),
+        ExecuteMsg::SetOracle { oracle } => execute_set_oracle(deps, env, info, oracle),
+        ExecuteMsg::UpdateFeeConfig { fee_config } => execute_update_fee_config(deps, env, info, fee_config),
+        ExecuteMsg::UpdateRateLimits { rate_limits } => execute_update_rate_limits(deps, env, info, rate_limits),
+        ExecuteMsg::SetRedeemWindow { source_contract, redeem_start, redeem_end } => execute_set_redeem_window(deps, env, info, source_contract, RedeemWindow { redeem_start, redeem_end }),
+        ExecuteMsg::Pause {} => execute_pause(deps, env, info),
+        ExecuteMsg::Unpause {} => execute_unpause(deps, env, info),
+        ExecuteMsg::UpdateCircuitBreaker { circuit_breaker } => execute_update_circuit_breaker(deps, env, info, circuit_breaker),
+        ExecuteMsg::SetReceiptContract { receipt_contract } => execute_set_receipt_contract(deps, env, info, receipt_contract),
+        ExecuteMsg::SetAcceptedDenoms { accepted_denoms } => execute_set_accepted_denoms(deps, env, info, accepted_denoms),
+        ExecuteMsg::RegisterCw20Tokens { cw20_tokens } => execute_register_cw20_tokens(deps, env, info, cw20_tokens),
+        ExecuteMsg::Sweep { cw20_tokens } => execute_sweep(deps, env, info, cw20_tokens),
+        ExecuteMsg::RegisterIssuer { issuer, public_key } => execute_register_issuer(deps, env, info, issuer, public_key),
+        ExecuteMsg::RemoveIssuer { issuer } => execute_remove_issuer(deps, env, info, issuer),
+        ExecuteMsg::RedeemVoucher { voucher, signature } => execute_redeem_voucher(deps, env, info, voucher, signature),
+        ExecuteMsg::SetPriceCeilings { source_contract, ceilings } => execute_set_price_ceilings(deps, env, info, source_contract, ceilings),
     }
 }
 
 pub fn execute_deposit(
-    mut _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     info: MessageInfo,
+    source_contract: Option<String>,
 ) -> Result<Response, ContractError> {
     if info.funds.is_empty() {
         return Err(ContractError::InvalidParameter { msg: "amount is empty.".to_string() });
     }
 
+    // the CW20 tokens are checked by `execute_receive`, where the registered ones are accepted as well
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if let Some(accepted_denoms) = contract_info.accepted_denoms.as_ref() {
+        if let Some(coin) = info.funds.iter().find(|coin| !accepted_denoms.contains(&coin.denom)) {
+            return Err(ContractError::UnacceptedDenom { denom: coin.denom.to_string() });
+        }
+    }
+
+    deposit(deps, info.sender.to_string(), source_contract, info.funds)
+}
+
+/// Deposit CW20 tokens. `info.sender` is the CW20 contract.
+/// Only the CW20 tokens registered by the payer or listed in the accepted denoms can be deposited.
+pub fn execute_receive(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    if msg.amount.is_zero() {
+        return Err(ContractError::InvalidParameter { msg: "amount is empty.".to_string() });
+    }
+
+    match from_binary(&msg.msg)? {
+        ReceiveMsg::Deposit { source_contract } => {
+            let contract_info = CONTRACT_INFO.load(deps.storage)?;
+            let accepted = contract_info.accepted_denoms.as_ref()
+                .map_or(false, |accepted_denoms| accepted_denoms.contains(&info.sender.to_string()));
+            if !accepted && CW20_TOKENS.may_load(deps.storage, info.sender.as_str())?.is_none() {
+                return Err(ContractError::UnacceptedDenom { denom: info.sender.to_string() });
+            }
+            CW20_TOKENS.save(deps.storage, info.sender.as_str(), &Empty {})?;
+            let funds = vec![Asset::Cw20(Cw20Coin { address: info.sender.to_string(), amount: msg.amount }).to_coin()];
+            deposit(deps, msg.sender, source_contract, funds)
+        }
+    }
+}
+
+fn deposit(
+    deps: DepsMut,
+    sender: String,
+    source_contract: Option<String>,
+    funds: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if let Some(source_contract) = source_contract.as_ref() {
+        if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
+            return Err(ContractError::InvalidParameter { msg: format!("unexpected source contract. actual: {}, expected: {}", source_contract, contract_info.source_contracts.join(",")) });
+        }
+    }
+
+    for coin in funds.iter() {
+        add_contribution(deps.storage, &contract_info, sender.as_str(), coin)?;
+        credit_budget(deps.storage, source_contract.as_deref(), coin)?;
+    }
+
     Ok(Response {
         submessages: vec![],
         messages: vec![],
         attributes: vec![
             attr("action", "deposit"),
-            attr("sender", info.sender),
+            attr("sender", sender),
+            attr("source_contract", source_contract.unwrap_or_default()),
+            attr("amount", funds.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
         ],
         data: None,
     })
 }
 
-pub fn execute_receive_nft(
+pub fn execute_allocate(
     deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    source_contract: String,
+    amount: Coin,
+) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
+        return Err(ContractError::InvalidParameter { msg: format!("unexpected source contract. actual: {}, expected: {}", source_contract, contract_info.source_contracts.join(",")) });
+    }
+    if amount.amount.is_zero() {
+        return Err(ContractError::InvalidParameter { msg: "amount is empty.".to_string() });
+    }
+
+    debit_budget(deps.storage, None, &amount)?;
+    credit_budget(deps.storage, Some(source_contract.as_str()), &amount)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "allocate"),
+            attr("source_contract", source_contract),
+            attr("amount", amount),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_receive_nft(
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: Cw721ReceiveMsg,
@@ -74,6 +211,22 @@ pub fn execute_receive_nft(
     let token_id = msg.token_id.to_string();
     let owner_of: OwnerOfResponse = from_binary(&msg.msg.unwrap_or_default())?;
 
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
+        return Err(ContractError::Unauthorized {
+            msg: format!("The token belongs to an unexpected contract. actual: {}, expected: {}", source_contract.as_str(), contract_info.source_contracts.join(",")),
+        });
+    }
+
+    if let Some(paused) = PAUSED.may_load(deps.storage)? {
+        let reason = ContractError::Paused { reason: paused.reason };
+        return bounce_nft(source_contract.as_str(), msg.sender.as_str(), token_id.as_str(), reason);
+    }
+    if !load_redeem_window(deps.storage, &contract_info, source_contract.as_str())?.contains(env.block.time) {
+        let reason = ContractError::OutsideRedeemWindow { time: env.block.time };
+        return bounce_nft(source_contract.as_str(), msg.sender.as_str(), token_id.as_str(), reason);
+    }
+
     let token_owner_info: TokenOwnerInfo = TokenOwnerInfo {
         sender: msg.sender.to_string(),
         owner_of,
@@ -84,13 +237,6 @@ pub fn execute_receive_nft(
         return Err(ContractError::Std(res.unwrap_err()))
     }
 
-    let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
-        return Err(ContractError::Unauthorized {
-            msg: format!("The token belongs to an unexpected contract. actual: {}, expected: {}", source_contract.as_str(), contract_info.source_contracts.join(",")),
-        });
-    }
-
     let query_msg = cw721_base::msg::QueryMsg::AllNftInfo {
         token_id: token_id.to_string(),
         include_expired: None,
@@ -101,301 +247,3829 @@ pub fn execute_receive_nft(
         return Err(ContractError::Unauthorized { msg: "The owner of the token must be this contract.".to_string() });
     }
 
-    let token_info: TokenInfo = from_slice(all_nft_info.info.description.as_bytes()).unwrap();
-    let token_price = token_info.price;
+    let redeemed = match redeem_token(deps.branch(), &env, source_contract.as_str(), msg.sender.as_str(), token_id.as_str(), all_nft_info.info.description.as_str(), &[]) {
+        Err(ContractError::Paused { reason }) => {
+            TOKEN_OWNER_INFO.remove(deps.storage, (source_contract.to_string(), token_id.to_string()));
+            return bounce_nft(source_contract.as_str(), msg.sender.as_str(), token_id.as_str(), ContractError::Paused { reason });
+        }
+        res => res?,
+    };
 
-    let deposit_coin = deps.querier.query_balance(env.contract.address, token_price.denom.clone())?;
-    if deposit_coin.amount.lt(token_price.amount.borrow()) {
-        return Err(ContractError::InsufficientDeposit {});
+    let mut messages = vec![];
+    let net = redeemed.net();
+    if !net.amount.is_zero() {
+        messages.append(&mut send_msgs(deps.storage, msg.sender.as_str(), vec![net])?);
+    }
+    if let (Some(fee_config), Some(fee)) = (FEE_CONFIG.may_load(deps.storage)?, redeemed.fee.as_ref()) {
+        if !fee.amount.is_zero() {
+            messages.append(&mut send_msgs(deps.storage, fee_config.treasury.as_str(), vec![fee.clone()])?);
+        }
     }
-
-    let execute_bank_send_msg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: msg.sender.to_string(),
-        amount: vec![token_price.clone()],
-    });
     let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft {
-        recipient: contract_info.payer,
+        recipient: contract_info.payer.to_string(),
         token_id: token_id.to_string(),
     };
 
-    let execute_wasm_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
         contract_addr: source_contract.to_string(),
         msg: to_binary(&transfer_msg)?,
         send: vec![],
-    });
+    }));
+    messages.append(&mut receipt_msgs(deps.storage, &env, &contract_info, msg.sender.as_str(), source_contract.as_str(), token_id.as_str(), &redeemed.paid)?);
+
+    let mut attributes = vec![
+        attr("action", "receive_nft"),
+        attr("sender", msg.sender.to_string()),
+        attr("sender_contract", info.sender.to_string()),
+        attr("token_id", msg.token_id.to_string()),
+    ];
+    attributes.append(&mut redeemed.attributes());
 
     Ok(Response {
         submessages: vec![],
-        messages: vec![execute_bank_send_msg, execute_wasm_msg],
-        attributes: vec![
-            attr("action", "receive_nft"),
-            attr("sender", msg.sender.to_string()),
-            attr("sender_contract", info.sender.to_string()),
-            attr("token_id", msg.token_id.to_string()),
-            attr("price", token_price),
-        ],
+        messages,
+        attributes,
         data: None,
     })
 }
 
-fn is_invalid_from_contract(contract_info: &ContractInfoResponse, source_contract: String) -> bool {
-    contract_info.source_contracts
-        .iter()
-        .any(|x| x.eq(source_contract.as_str())) == false
-}
+/// Redeems the tokens of `source_contract` owned by the sender, which approved this contract as an operator.
+/// If the circuit breaker trips, the remaining tokens are left to the sender.
+pub fn execute_redeem_batch(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    source_contract: String,
+    token_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
+        return Err(ContractError::Unauthorized {
+            msg: format!("The token belongs to an unexpected contract. actual: {}, expected: {}", source_contract.as_str(), contract_info.source_contracts.join(",")),
+        });
+    }
+    if token_ids.is_empty() {
+        return Err(ContractError::InvalidParameter { msg: "token_ids is empty.".to_string() });
+    }
+    if let Some(paused) = PAUSED.may_load(deps.storage)? {
+        return Err(ContractError::Paused { reason: paused.reason });
+    }
+    if !load_redeem_window(deps.storage, &contract_info, source_contract.as_str())?.contains(env.block.time) {
+        return Err(ContractError::OutsideRedeemWindow { time: env.block.time });
+    }
 
-pub fn execute_recover_owner(deps: DepsMut,
-                             _env: Env,
-                             _info: MessageInfo,
-                             contract: String,
-                             token_id: String) -> Result<Response, ContractError> {
-    let res = TOKEN_OWNER_INFO.load(deps.storage, (contract.to_string(), token_id.to_string()));
-    if res.is_err() {
-        return Err(ContractError::Std(res.unwrap_err()));
+    let mut payout = vec![];
+    let mut fees = vec![];
+    let mut messages = vec![];
+    let mut attributes = vec![
+        attr("action", "redeem_batch"),
+        attr("sender", info.sender.to_string()),
+        attr("sender_contract", source_contract.to_string()),
+    ];
+    let mut descriptions = vec![];
+    for (i, token_id) in token_ids.iter().enumerate() {
+        if token_ids[..i].contains(token_id) {
+            return Err(ContractError::InvalidParameter { msg: format!("duplicated token_id: {}", token_id) });
+        }
+
+        let query_msg = cw721_base::msg::QueryMsg::AllNftInfo {
+            token_id: token_id.to_string(),
+            include_expired: None,
+        };
+        let all_nft_info: AllNftInfoResponse = deps.querier.query_wasm_smart(source_contract.as_str(), &query_msg)?;
+        if info.sender.as_str().ne(all_nft_info.access.owner.as_str()) {
+            return Err(ContractError::Unauthorized { msg: format!("The owner of the token {} must be the sender.", token_id) });
+        }
+        descriptions.push(all_nft_info.info.description);
     }
-    let token_owner_info = res.unwrap();
-    let sender = token_owner_info.sender;
 
-    let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft {
-        recipient: sender.to_string(),
-        token_id: token_id.to_string(),
-    };
+    for (token_id, description) in token_ids.iter().zip(descriptions.iter()) {
+        let mut pending = payout.clone();
+        for fee in fees.iter() {
+            add_coin(&mut pending, fee)?;
+        }
+        let redeemed = match redeem_token(deps.branch(), &env, source_contract.as_str(), info.sender.as_str(), token_id.as_str(), description.as_str(), &pending) {
+            Err(ContractError::Paused { reason }) => {
+                attributes.push(attr("paused", reason));
+                break;
+            }
+            res => res?,
+        };
 
-    let execute_wasm_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: contract.to_string(),
-        msg: to_binary(&transfer_msg)?,
-        send: vec![],
-    });
+        let net = redeemed.net();
+        if !net.amount.is_zero() {
+            add_coin(&mut payout, &net)?;
+        }
+        if let Some(fee) = redeemed.fee.as_ref().filter(|fee| !fee.amount.is_zero()) {
+            add_coin(&mut fees, fee)?;
+        }
+        let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft {
+            recipient: contract_info.payer.to_string(),
+            token_id: token_id.to_string(),
+        };
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: source_contract.to_string(),
+            msg: to_binary(&transfer_msg)?,
+            send: vec![],
+        }));
+        messages.append(&mut receipt_msgs(deps.storage, &env, &contract_info, info.sender.as_str(), source_contract.as_str(), token_id.as_str(), &redeemed.paid)?);
 
+        attributes.push(attr("token_id", token_id));
+        attributes.append(&mut redeemed.attributes());
+    }
 
-    return Ok(Response {
+    let mut send = vec![];
+    if !payout.is_empty() {
+        attributes.push(attr("amount", payout.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+        send.append(&mut send_msgs(deps.storage, info.sender.as_str(), payout)?);
+    }
+    if let Some(fee_config) = FEE_CONFIG.may_load(deps.storage)?.filter(|_| !fees.is_empty()) {
+        send.append(&mut send_msgs(deps.storage, fee_config.treasury.as_str(), fees)?);
+    }
+    send.append(&mut messages);
+
+    Ok(Response {
         submessages: vec![],
-        messages: vec![execute_wasm_msg],
-        attributes: vec![
-            attr("action", "recover_owner"),
-            attr("sender", sender.to_string()),
-            attr("sender_contract", contract.to_string()),
-            attr("token_id", token_id.to_string()),
-        ],
+        messages: send,
+        attributes,
         data: None,
-    });
+    })
 }
 
-pub fn execute_refund(deps: DepsMut,
-                      env: Env,
-                      info: MessageInfo) -> Result<Response, ContractError> {
+/// Payout of a redeemed token
+struct RedeemedToken {
+    face_value: Coin,
+    /// Face value converted into the payout denom, and the applied rate
+    conversion: Option<(Coin, Decimal)>,
+    paid: Coin,
+    fee: Option<Coin>,
+    /// Claim of the part of the payout that could not be paid
+    claim: Option<Claim>,
+}
+
+impl RedeemedToken {
+    /// Paid amount without the fee
+    fn net(&self) -> Coin {
+        let fee = self.fee.as_ref().map_or_else(Uint128::zero, |fee| fee.amount);
+        Coin { denom: self.paid.denom.clone(), amount: self.paid.amount.saturating_sub(fee) }
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let mut attributes = vec![attr("price", self.face_value.clone())];
+        if let Some((payout, rate)) = self.conversion.as_ref() {
+            attributes.push(attr("rate", rate));
+            attributes.push(attr("payout", payout.clone()));
+        }
+        if let Some(fee) = self.fee.as_ref() {
+            attributes.push(attr("fee", fee.clone()));
+        }
+        if let Some(claim) = self.claim.as_ref() {
+            attributes.push(attr("paid", self.paid.clone()));
+            attributes.push(attr("outstanding", claim.outstanding.clone()));
+            attributes.push(attr("claim_id", claim.id));
+        }
+        attributes
+    }
+}
+
+/// Redeems a token of `source_contract` described by `description`, and records the redemption.
+/// `pending` are the payouts that are not sent yet. Returns `ContractError::Paused` if the circuit breaker trips.
+fn redeem_token(deps: DepsMut,
+                env: &Env,
+                source_contract: &str,
+                sender: &str,
+                token_id: &str,
+                description: &str,
+                pending: &[Coin]) -> Result<RedeemedToken, ContractError> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    let payer = contract_info.payer;
-    if info.sender.as_str().ne(payer.as_str()) {
-        return Err(ContractError::UnmatchedPayer {});
+    let token_info = parse_token_info(description)?;
+    let face_value = token_info.price.to_coin();
+    check_price_ceiling(deps.as_ref(), source_contract, &face_value)?;
+    let (token_price, rate) = convert(deps.as_ref(), &face_value)?;
+
+    let deposit_coin = query_deposit(deps.as_ref(), env, token_price.denom.as_str())?;
+    let pending = pending.iter()
+        .find(|c| c.denom == token_price.denom)
+        .map_or_else(Uint128::zero, |c| c.amount);
+    let budget = BUDGETS.may_load(deps.storage, (source_contract, token_price.denom.as_str()))?
+        .map_or_else(Uint128::zero, |budget| budget.amount);
+    let queued = OUTSTANDING.may_load(deps.storage, (source_contract, token_price.denom.as_str()))?
+        .map_or_else(Uint128::zero, |outstanding| outstanding.amount);
+    let available = budget.saturating_sub(queued).min(deposit_coin.amount.saturating_sub(pending));
+    if available.lt(token_price.amount.borrow()) && !contract_info.iou_enabled {
+        return Err(ContractError::InsufficientDeposit {});
+    }
+
+    let paid = Coin { denom: token_price.denom.clone(), amount: available.min(token_price.amount) };
+    let outstanding = Coin { denom: token_price.denom.clone(), amount: token_price.amount.saturating_sub(paid.amount) };
+    if let Some(paused) = check_circuit_breaker(deps.storage, env, &paid)? {
+        return Err(ContractError::Paused { reason: paused.reason });
+    }
+    check_rate_limits(deps.storage, env, sender, &paid, true)?;
+    record_circuit_breaker(deps.storage, env, &paid)?;
+    record_rate_limits(deps.storage, env, sender, &paid, true)?;
+    if !paid.amount.is_zero() {
+        debit_budget(deps.storage, Some(source_contract), &paid)?;
     }
+    REDEEMED.update(deps.storage, (source_contract, face_value.denom.as_str()), |redeemed| -> StdResult<Coin> {
+        let mut redeemed = redeemed.unwrap_or_else(|| Coin::new(0, face_value.denom.clone()));
+        redeemed.amount = redeemed.amount.checked_add(face_value.amount)?;
+        Ok(redeemed)
+    })?;
 
-    let balances = deps.querier.query_all_balances(env.contract.address)?;
+    let fee = match FEE_CONFIG.may_load(deps.storage)? {
+        Some(fee_config) if !paid.amount.is_zero() => Some(calculate_fee(&fee_config, &paid, true)),
+        _ => None,
+    };
 
-    let execute_bank_send_msg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: balances.clone(),
-    });
+    let claim = if outstanding.amount.is_zero() {
+        None
+    } else {
+        let claim = Claim {
+            id: CLAIM_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1,
+            sender: sender.to_string(),
+            source_contract: source_contract.to_string(),
+            token_id: token_id.to_string(),
+            outstanding: outstanding.clone(),
+            redeemed_at: env.block.time,
+        };
+        CLAIM_SEQ.save(deps.storage, &claim.id)?;
+        save_claim(deps.storage, &claim)?;
+        OUTSTANDING.update(deps.storage, (source_contract, outstanding.denom.as_str()), |queued| -> StdResult<Coin> {
+            let mut queued = queued.unwrap_or_else(|| Coin::new(0, outstanding.denom.clone()));
+            queued.amount = queued.amount.checked_add(outstanding.amount)?;
+            Ok(queued)
+        })?;
+        Some(claim)
+    };
 
-    return Ok(Response {
+    Ok(RedeemedToken {
+        face_value,
+        conversion: rate.map(|rate| (token_price, rate)),
+        paid,
+        fee,
+        claim,
+    })
+}
+
+/// Mints the receipt of a redemption on the receipt contract, if it is set.
+fn receipt_msgs(storage: &dyn Storage,
+                env: &Env,
+                contract_info: &ContractInfoResponse,
+                owner: &str,
+                source_contract: &str,
+                token_id: &str,
+                paid: &Coin) -> StdResult<Vec<CosmosMsg>> {
+    let receipt_contract = match contract_info.receipt_contract.as_ref() {
+        Some(receipt_contract) => receipt_contract,
+        None => return Ok(vec![]),
+    };
+
+    let receipt = Receipt {
+        source_contract: source_contract.to_string(),
+        token_id: token_id.to_string(),
+        paid: paid.clone(),
+        redeemed_at: env.block.time,
+    };
+    Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: receipt_contract.to_string(),
+        msg: to_binary(&receipt_mint_msg(storage, owner, receipt)?)?,
+        send: vec![],
+    })])
+}
+
+/// Checks `face_value` against the price ceiling of `source_contract` and the price cap of its mint policy.
+fn check_price_ceiling(deps: Deps, source_contract: &str, face_value: &Coin) -> Result<(), ContractError> {
+    let mut ceilings = PRICE_CEILINGS.may_load(deps.storage, source_contract)?.unwrap_or_default();
+    ceilings.append(&mut query_price_caps(deps, source_contract).unwrap_or_default());
+
+    match ceilings.into_iter().filter(|ceiling| ceiling.denom == face_value.denom).min_by_key(|ceiling| ceiling.amount) {
+        Some(ceiling) if face_value.amount > ceiling.amount => Err(ContractError::PriceExceedsCeiling { price: face_value.clone(), ceiling }),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the price caps of the mint policy of `source_contract`, or `None` if it does not provide its mint policy.
+fn query_price_caps(deps: Deps, source_contract: &str) -> Option<Vec<Coin>> {
+    deps.querier.query_wasm_smart::<MintPolicyResponse, _, _>(source_contract, &SourceQueryMsg::MintPolicy {})
+        .ok()
+        .map(|mint_policy| mint_policy.price_caps)
+}
+
+/// Sends the received token back to the sender instead of redeeming it.
+fn bounce_nft(source_contract: &str, sender: &str, token_id: &str, reason: ContractError) -> Result<Response, ContractError> {
+    let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft {
+        recipient: sender.to_string(),
+        token_id: token_id.to_string(),
+    };
+
+    Ok(Response {
         submessages: vec![],
-        messages: vec![execute_bank_send_msg],
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: source_contract.to_string(),
+            msg: to_binary(&transfer_msg)?,
+            send: vec![],
+        })],
         attributes: vec![
-            attr("action", "refund"),
-            attr("sender_contract", info.sender.to_string()),
-            attr("refund", balances.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+            attr("action", "bounce_nft"),
+            attr("sender", sender),
+            attr("sender_contract", source_contract),
+            attr("token_id", token_id),
+            attr("reason", reason.to_string()),
         ],
         data: None,
-    });
+    })
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::ContractInfo {} => to_binary(&query_contract_info(deps)?),
+/// The receipt is priced at zero in the denom of the payout, so it is worth nothing when redeemed.
+fn receipt_mint_msg(storage: &dyn Storage, owner: &str, receipt: Receipt) -> StdResult<ReceiptExecuteMsg> {
+    let price = if CW20_TOKENS.may_load(storage, receipt.paid.denom.as_str())?.is_some() {
+        Asset::Cw20(Cw20Coin { address: receipt.paid.denom.to_string(), amount: Uint128::zero() })
+    } else {
+        Asset::Native(Coin::new(0, receipt.paid.denom.to_string()))
+    };
+
+    Ok(ReceiptExecuteMsg::Mint(ReceiptMintMsg {
+        owner: owner.to_string(),
+        name: format!("receipt of {} {}", receipt.source_contract, receipt.token_id),
+        description: None,
+        image: None,
+        price,
+        receipt: Some(receipt),
+    }))
+}
+
+fn validate_receipt_contract(contract_info: &ContractInfoResponse, receipt_contract: Option<&String>) -> Result<(), ContractError> {
+    if let Some(receipt_contract) = receipt_contract {
+        if !is_invalid_from_contract(contract_info, receipt_contract.to_string()) {
+            return Err(ContractError::InvalidParameter { msg: "receipt_contract must not be a source contract.".to_string() });
+        }
     }
+    Ok(())
 }
 
-fn query_contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
-    CONTRACT_INFO.load(deps.storage)
+fn load_redeem_window(storage: &dyn Storage, contract_info: &ContractInfoResponse, source_contract: &str) -> StdResult<RedeemWindow> {
+    Ok(REDEEM_WINDOWS.may_load(storage, source_contract)?.unwrap_or(RedeemWindow {
+        redeem_start: contract_info.redeem_start,
+        redeem_end: contract_info.redeem_end,
+    }))
 }
 
-#[cfg(test)]
-mod tests {
-    use cosmwasm_std::coin;
-    use cosmwasm_std::testing::mock_dependencies;
-    use cosmwasm_vm::testing::{mock_env, mock_info};
+fn validate_redeem_window(redeem_window: &RedeemWindow) -> Result<(), ContractError> {
+    if let (Some(redeem_start), Some(redeem_end)) = (redeem_window.redeem_start, redeem_window.redeem_end) {
+        if redeem_start >= redeem_end {
+            return Err(ContractError::InvalidParameter { msg: "redeem_start must be before redeem_end.".to_string() });
+        }
+    }
+    Ok(())
+}
 
-    use crate::msg::TokenInfoMsg;
+fn is_invalid_from_contract(contract_info: &ContractInfoResponse, source_contract: String) -> bool {
+    contract_info.source_contracts
+        .iter()
+        .any(|x| x.eq(source_contract.as_str())) == false
+}
 
-    use super::*;
+/// Converts `face_value` into the payout by the conversion of its denom.
+/// Returns the payout and the applied rate, or `face_value` itself if there is no conversion.
+fn convert(deps: Deps, face_value: &Coin) -> Result<(Coin, Option<Decimal>), ContractError> {
+    let conversion = match CONVERSIONS.may_load(deps.storage, face_value.denom.as_str())? {
+        Some(conversion) => conversion,
+        None => return Ok((face_value.clone(), None)),
+    };
 
-    #[test]
-    fn proper_instantiate() {
-        let mut deps = mock_dependencies(&[]);
-        let env = mock_env();
-        let msg = InstantiateMsg {
-            source_contracts: vec!["contract1".to_string()],
-        };
+    let rate = exchange_rate(deps, &conversion)?;
+    let payout = Coin { denom: conversion.to_denom, amount: face_value.amount * rate };
+    Ok((payout, Some(rate)))
+}
 
-        let info = mock_info("creator", &[]);
+fn exchange_rate(deps: Deps, conversion: &Conversion) -> Result<Decimal, ContractError> {
+    let rate = match (conversion.rate, ORACLE.may_load(deps.storage)?) {
+        (Some(rate), _) => rate,
+        (None, Some(oracle)) => {
+            let res: ExchangeRateResponse = deps.querier.query_wasm_smart(oracle, &OracleQueryMsg::ExchangeRate {
+                base_denom: conversion.from_denom.to_string(),
+                quote_denom: conversion.to_denom.to_string(),
+            })?;
+            res.rate
+        }
+        (None, None) => return Err(ContractError::NoExchangeRate {
+            from_denom: conversion.from_denom.to_string(),
+            to_denom: conversion.to_denom.to_string(),
+        }),
+    };
 
-        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
-        assert!(res.is_ok());
+    if conversion.min_rate.map_or(false, |min_rate| rate < min_rate)
+        || conversion.max_rate.map_or(false, |max_rate| rate > max_rate) {
+        return Err(ContractError::ExchangeRateOutOfBounds { rate });
     }
+    Ok(rate)
+}
 
-    #[test]
-    fn receive_nft() {
-        let mut deps = mock_dependencies(&[]);
-        let env = mock_env();
-        let info = mock_info("creator", &[]);
-        let msg = InstantiateMsg {
-            source_contracts: vec!["contract1".to_string()],
-        };
+/// Checks `paid` against the threshold of the circuit breaker without counting it.
+/// If the payouts within the window and `paid` exceed the threshold, pauses the contract and returns the pause.
+fn check_circuit_breaker(storage: &mut dyn Storage, env: &Env, paid: &Coin) -> StdResult<Option<PauseInfo>> {
+    let circuit_breaker = match CIRCUIT_BREAKER.may_load(storage)? {
+        Some(circuit_breaker) => circuit_breaker,
+        None => return Ok(None),
+    };
+    if paid.amount.is_zero() {
+        return Ok(None);
+    }
 
-        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
-        assert!(res.is_ok());
+    if let Some(threshold) = circuit_breaker.threshold.iter().find(|c| c.denom == paid.denom) {
+        let payout = load_window_payout(storage, env, &circuit_breaker)?.iter()
+            .filter(|c| c.denom == paid.denom)
+            .try_fold(paid.amount, |sum, c| sum.checked_add(c.amount))?;
+        if payout > threshold.amount {
+            let paused = PauseInfo {
+                reason: format!("circuit breaker tripped. payouts exceeded {} per {} seconds", threshold, circuit_breaker.window_seconds),
+                paused_at: env.block.time,
+            };
+            PAUSED.save(storage, &paused)?;
+            return Ok(Some(paused));
+        }
+    }
+    Ok(None)
+}
 
-        let receive_info = mock_info("contract1", &[]);
-        let owner_of_msg = OwnerOfResponse {
-            owner: "sender".to_string(),
-            approvals: vec![],
-        };
+/// Counts the committed payout `paid` in the window of the circuit breaker.
+fn record_circuit_breaker(storage: &mut dyn Storage, env: &Env, paid: &Coin) -> StdResult<()> {
+    let circuit_breaker = match CIRCUIT_BREAKER.may_load(storage)? {
+        Some(circuit_breaker) => circuit_breaker,
+        None => return Ok(()),
+    };
+    if paid.amount.is_zero() {
+        return Ok(());
+    }
 
-        let receive_msg = Cw721ReceiveMsg {
-            sender: "sender".to_string(),
+    let (first_slot, slot) = payout_slots(env, &circuit_breaker);
+    // the slots before the window are not counted anymore
+    let expired = PAYOUT_SLOTS
+        .keys(storage, None, Some(Bound::exclusive_int(first_slot)), Order::Ascending)
+        .collect::<Vec<Vec<u8>>>();
+    for key in expired {
+        PAYOUT_SLOTS.remove(storage, U64Key::from(key));
+    }
+
+    PAYOUT_SLOTS.update(storage, U64Key::new(slot), |payout| -> StdResult<Vec<Coin>> {
+        let mut payout = payout.unwrap_or_default();
+        add_coin(&mut payout, paid)?;
+        Ok(payout)
+    })?;
+    Ok(())
+}
+
+/// Returns the first slot of the window of `circuit_breaker` and the current slot.
+fn payout_slots(env: &Env, circuit_breaker: &CircuitBreaker) -> (u64, u64) {
+    let slot_seconds = (circuit_breaker.window_seconds / CIRCUIT_BREAKER_SLOTS).max(1);
+    let now = env.block.time.seconds();
+    (now.saturating_sub(circuit_breaker.window_seconds) / slot_seconds + 1, now / slot_seconds)
+}
+
+/// Returns the payouts of all senders within the window of `circuit_breaker`.
+fn load_window_payout(storage: &dyn Storage, env: &Env, circuit_breaker: &CircuitBreaker) -> StdResult<Vec<Coin>> {
+    let (first_slot, _) = payout_slots(env, circuit_breaker);
+    let mut window_payout = vec![];
+    for item in PAYOUT_SLOTS.range(storage, Some(Bound::inclusive_int(first_slot)), None, Order::Ascending) {
+        let (_, payout) = item?;
+        for coin in payout.iter() {
+            add_coin(&mut window_payout, coin)?;
+        }
+    }
+    Ok(window_payout)
+}
+
+fn clear_payout_slots(storage: &mut dyn Storage) {
+    let slots = PAYOUT_SLOTS
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<Vec<Vec<u8>>>();
+    for key in slots {
+        PAYOUT_SLOTS.remove(storage, U64Key::from(key));
+    }
+}
+
+/// Checks the rate limits for the payout `paid` to `sender` without counting it.
+/// `redemption` is false for the payout of a claim, which is not counted as another redemption.
+fn check_rate_limits(storage: &dyn Storage, env: &Env, sender: &str, paid: &Coin, redemption: bool) -> Result<(), ContractError> {
+    let rate_limits = match RATE_LIMITS.may_load(storage)? {
+        Some(rate_limits) => rate_limits,
+        None => return Ok(()),
+    };
+
+    let mut usage = load_rate_usage(storage, env, sender, &rate_limits)?;
+    if let Some(max_redemptions) = rate_limits.max_redemptions_per_day.filter(|_| redemption) {
+        if usage.redemptions >= max_redemptions {
+            return Err(ContractError::RateLimitExceeded { msg: format!("max {} redemptions per day", max_redemptions) });
+        }
+    }
+    add_coin(&mut usage.payout, paid)?;
+    if let Some(max_payout) = rate_limits.max_payout_per_window.iter().find(|c| c.denom == paid.denom) {
+        if usage.payout.iter().any(|c| c.denom == paid.denom && c.amount > max_payout.amount) {
+            return Err(ContractError::RateLimitExceeded { msg: format!("max payout {} per {} seconds", max_payout, rate_limits.window_seconds) });
+        }
+    }
+    let mut block_payout = load_block_payout(storage, env)?;
+    add_coin(&mut block_payout.amount, paid)?;
+    if let Some(max_payout) = rate_limits.max_payout_per_block.iter().find(|c| c.denom == paid.denom) {
+        if block_payout.amount.iter().any(|c| c.denom == paid.denom && c.amount > max_payout.amount) {
+            return Err(ContractError::RateLimitExceeded { msg: format!("max payout {} per block", max_payout) });
+        }
+    }
+    Ok(())
+}
+
+/// Counts the committed payout `paid` to `sender` in the rate limits.
+fn record_rate_limits(storage: &mut dyn Storage, env: &Env, sender: &str, paid: &Coin, redemption: bool) -> StdResult<()> {
+    let rate_limits = match RATE_LIMITS.may_load(storage)? {
+        Some(rate_limits) => rate_limits,
+        None => return Ok(()),
+    };
+
+    let mut usage = load_rate_usage(storage, env, sender, &rate_limits)?;
+    add_coin(&mut usage.payout, paid)?;
+    if redemption {
+        usage.redemptions += 1;
+    }
+    let mut block_payout = load_block_payout(storage, env)?;
+    add_coin(&mut block_payout.amount, paid)?;
+
+    RATE_USAGE.save(storage, sender, &usage)?;
+    BLOCK_PAYOUT.save(storage, &block_payout)
+}
+
+/// Returns the redemptions of `sender` in the current day and the payouts in the current window of `rate_limits`.
+fn load_rate_usage(storage: &dyn Storage, env: &Env, sender: &str, rate_limits: &RateLimits) -> StdResult<RateUsage> {
+    let day = env.block.time.seconds() / SECONDS_PER_DAY;
+    let window = env.block.time.seconds() / rate_limits.window_seconds.max(1);
+    let mut usage = RATE_USAGE.may_load(storage, sender)?
+        .unwrap_or(RateUsage { day, redemptions: 0, window, payout: vec![] });
+    if usage.day != day {
+        usage.day = day;
+        usage.redemptions = 0;
+    }
+    if usage.window != window {
+        usage.window = window;
+        usage.payout = vec![];
+    }
+    Ok(usage)
+}
+
+fn load_block_payout(storage: &dyn Storage, env: &Env) -> StdResult<BlockPayout> {
+    Ok(BLOCK_PAYOUT.may_load(storage)?
+        .filter(|block_payout| block_payout.height == env.block.height)
+        .unwrap_or(BlockPayout { height: env.block.height, amount: vec![] }))
+}
+
+/// Returns the fee of `payout`, which is not greater than the payout.
+fn calculate_fee(fee_config: &FeeConfig, payout: &Coin, with_flat: bool) -> Coin {
+    let mut amount = payout.amount * fee_config.percent;
+    if with_flat {
+        if let Some(flat) = fee_config.flat.iter().find(|c| c.denom == payout.denom) {
+            amount += flat.amount;
+        }
+    }
+    Coin { denom: payout.denom.clone(), amount: amount.min(payout.amount) }
+}
+
+fn parse_token_info(description: &str) -> StdResult<TokenInfo> {
+    from_slice(description.as_bytes()).or_else(|_| {
+        let legacy: LegacyTokenInfo = from_slice(description.as_bytes())?;
+        Ok(TokenInfo { price: Asset::Native(legacy.price) })
+    })
+}
+
+/// Returns the balance of this contract in `denom`, which is a native denom or a CW20 contract address.
+fn query_deposit(deps: Deps, env: &Env, denom: &str) -> StdResult<Coin> {
+    if CW20_TOKENS.may_load(deps.storage, denom)?.is_none() {
+        return deps.querier.query_balance(env.contract.address.to_string(), denom);
+    }
+
+    let balance: BalanceResponse = deps.querier.query_wasm_smart(denom, &Cw20QueryMsg::Balance {
+        address: env.contract.address.to_string(),
+    })?;
+    Ok(Coin { denom: denom.to_string(), amount: balance.balance })
+}
+
+/// Returns all balances of this contract, in the native coins and the known CW20 tokens.
+fn query_balances(deps: Deps, env: &Env) -> StdResult<Vec<Coin>> {
+    let mut balances = deps.querier.query_all_balances(env.contract.address.to_string())?;
+    let cw20_tokens = CW20_TOKENS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(String::from_utf8)
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(StdError::from)?;
+    for cw20_token in cw20_tokens {
+        // a CW20 contract failing to answer must not block the refund
+        if let Ok(balance) = query_deposit(deps, env, cw20_token.as_str()) {
+            if !balance.amount.is_zero() {
+                balances.push(balance);
+            }
+        }
+    }
+    Ok(balances)
+}
+
+/// Returns the messages sending `coins` to `to_address`.
+/// The native coins are sent at once and the CW20 tokens are transferred one by one.
+fn send_msgs(storage: &dyn Storage, to_address: &str, coins: Vec<Coin>) -> StdResult<Vec<CosmosMsg>> {
+    let mut native = vec![];
+    let mut messages = vec![];
+    for coin in coins {
+        if CW20_TOKENS.may_load(storage, coin.denom.as_str())?.is_none() {
+            native.push(coin);
+            continue;
+        }
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: coin.denom,
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: to_address.to_string(),
+                amount: coin.amount,
+            })?,
+            send: vec![],
+        }));
+    }
+
+    if !native.is_empty() {
+        messages.insert(0, CosmosMsg::Bank(BankMsg::Send {
+            to_address: to_address.to_string(),
+            amount: native,
+        }));
+    }
+    Ok(messages)
+}
+
+/// Returns the messages refunding `coins` to `to_address`.
+/// The CW20 transfers are sent as submessages whose failure is ignored,
+/// so that a failing CW20 token does not block the refund of the other funds.
+fn refund_msgs(storage: &dyn Storage, to_address: &str, coins: Vec<Coin>) -> StdResult<(Vec<SubMsg>, Vec<CosmosMsg>)> {
+    let mut submessages = vec![];
+    let mut messages = vec![];
+    for msg in send_msgs(storage, to_address, coins)? {
+        match msg {
+            CosmosMsg::Wasm(_) => submessages.push(SubMsg { id: CW20_REFUND_REPLY_ID, msg, gas_limit: None, reply_on: ReplyOn::Error }),
+            _ => messages.push(msg),
+        }
+    }
+    Ok((submessages, messages))
+}
+
+/// Adds `coin` to the budget of `source_contract`, or to the unallocated funds if it is `None`.
+fn credit_budget(storage: &mut dyn Storage, source_contract: Option<&str>, coin: &Coin) -> StdResult<Coin> {
+    let add = |budget: Option<Coin>| -> StdResult<Coin> {
+        let mut budget = budget.unwrap_or_else(|| Coin::new(0, coin.denom.clone()));
+        budget.amount = budget.amount.checked_add(coin.amount)?;
+        Ok(budget)
+    };
+
+    match source_contract {
+        Some(source_contract) => BUDGETS.update(storage, (source_contract, coin.denom.as_str()), add),
+        None => UNALLOCATED.update(storage, coin.denom.as_str(), add),
+    }
+}
+
+/// Subtracts `coin` from the budget of `source_contract`, or from the unallocated funds if it is `None`.
+fn debit_budget(storage: &mut dyn Storage, source_contract: Option<&str>, coin: &Coin) -> Result<Coin, ContractError> {
+    let sub = |budget: Option<Coin>| -> Result<Coin, ContractError> {
+        let mut budget = budget.unwrap_or_else(|| Coin::new(0, coin.denom.clone()));
+        budget.amount = budget.amount.checked_sub(coin.amount).map_err(|_| ContractError::InsufficientDeposit {})?;
+        Ok(budget)
+    };
+
+    match source_contract {
+        Some(source_contract) => BUDGETS.update(storage, (source_contract, coin.denom.as_str()), sub),
+        None => UNALLOCATED.update(storage, coin.denom.as_str(), sub),
+    }
+}
+
+fn load_budget(storage: &dyn Storage, source_contract: Option<&str>) -> StdResult<Vec<Coin>> {
+    let budgets = match source_contract {
+        Some(source_contract) => BUDGETS.prefix(source_contract).range(storage, None, None, Order::Ascending),
+        None => UNALLOCATED.range(storage, None, None, Order::Ascending),
+    };
+
+    budgets
+        .map(|item| item.map(|(_, coin)| coin))
+        .filter(|item| item.as_ref().map_or(true, |coin| !coin.amount.is_zero()))
+        .collect()
+}
+
+/// Returns the face value of the tokens of `source_contract` that are not redeemed yet,
+/// or `None` if the source contract does not provide its face value supply.
+fn load_liability(deps: Deps, source_contract: &str) -> StdResult<Option<Vec<Coin>>> {
+    let supply: FaceValueSupplyResponse = match deps.querier.query_wasm_smart(source_contract, &SourceQueryMsg::FaceValueSupply {}) {
+        Ok(supply) => supply,
+        Err(_) => return Ok(None),
+    };
+    // the supply does not count the tokens minted before it was tracked
+    if !supply.initialized {
+        return Ok(None);
+    }
+
+    let mut liability = vec![];
+    for coin in supply.amount {
+        let redeemed = REDEEMED.may_load(deps.storage, (source_contract, coin.denom.as_str()))?
+            .map_or_else(Uint128::zero, |redeemed| redeemed.amount);
+        let unredeemed = Coin { denom: coin.denom, amount: coin.amount.saturating_sub(redeemed) };
+        if unredeemed.amount.is_zero() {
+            continue;
+        }
+
+        // reserve the unredeemed tokens in the denom they are paid in, at the highest allowed rate if the rate is unavailable
+        match CONVERSIONS.may_load(deps.storage, unredeemed.denom.as_str())? {
+            Some(conversion) => match exchange_rate(deps, &conversion).ok().or(conversion.max_rate) {
+                Some(rate) => add_coin(&mut liability, &Coin { denom: conversion.to_denom, amount: unredeemed.amount * rate })?,
+                None => return Ok(None),
+            },
+            None => add_coin(&mut liability, &unredeemed)?,
+        }
+    }
+    for item in OUTSTANDING.prefix(source_contract).range(deps.storage, None, None, Order::Ascending) {
+        let (_, queued) = item?;
+        if !queued.amount.is_zero() {
+            add_coin(&mut liability, &queued)?;
+        }
+    }
+    Ok(Some(liability))
+}
+
+/// Returns the funds that are not reserved for unredeemed tokens, per budget.
+/// The whole budget of a source contract is reserved if its liability is unknown.
+fn load_excess(deps: Deps, contract_info: &ContractInfoResponse) -> StdResult<Vec<(Option<String>, Coin)>> {
+    let mut excess: Vec<(Option<String>, Coin)> = load_budget(deps.storage, None)?
+        .into_iter()
+        .map(|coin| (None, coin))
+        .collect();
+
+    for source_contract in contract_info.source_contracts.iter() {
+        let liability = match load_liability(deps, source_contract)? {
+            Some(liability) => liability,
+            None => continue,
+        };
+        for coin in load_budget(deps.storage, Some(source_contract.as_str()))? {
+            let reserved = liability.iter()
+                .find(|c| c.denom == coin.denom)
+                .map_or_else(Uint128::zero, |c| c.amount);
+            let amount = coin.amount.saturating_sub(reserved);
+            if !amount.is_zero() {
+                excess.push((Some(source_contract.to_string()), Coin { denom: coin.denom, amount }));
+            }
+        }
+    }
+    Ok(excess)
+}
+
+/// Returns the funds of `denom` in the budgets and the unallocated funds.
+fn load_pool(storage: &dyn Storage, contract_info: &ContractInfoResponse, denom: &str) -> StdResult<Uint128> {
+    let mut pool = UNALLOCATED.may_load(storage, denom)?.map_or_else(Uint128::zero, |coin| coin.amount);
+    for source_contract in contract_info.source_contracts.iter() {
+        if let Some(budget) = BUDGETS.may_load(storage, (source_contract.as_str(), denom))? {
+            pool += budget.amount;
+        }
+    }
+    Ok(pool)
+}
+
+/// Records the deposit of `coin` by `depositor`, issuing shares at the current value of the funds.
+/// Must be called before the deposit is credited to a budget.
+fn add_contribution(storage: &mut dyn Storage, contract_info: &ContractInfoResponse, depositor: &str, coin: &Coin) -> StdResult<()> {
+    let pool = load_pool(storage, contract_info, coin.denom.as_str())?;
+    let total_shares = TOTAL_SHARES.may_load(storage, coin.denom.as_str())?.unwrap_or_default();
+    let shares = if total_shares.is_zero() || pool.is_zero() {
+        // the shares of the drained funds are worthless
+        clear_contributions(storage, coin.denom.as_str())?;
+        coin.amount
+    } else {
+        coin.amount.multiply_ratio(total_shares, pool)
+    };
+
+    DEPOSITORS.save(storage, depositor, &Empty {})?;
+    CONTRIBUTIONS.update(storage, (depositor, coin.denom.as_str()), |contribution| -> StdResult<Contribution> {
+        let mut contribution = contribution.unwrap_or(Contribution { deposited: Uint128::zero(), shares: Uint128::zero() });
+        contribution.deposited = contribution.deposited.checked_add(coin.amount)?;
+        contribution.shares = contribution.shares.checked_add(shares)?;
+        Ok(contribution)
+    })?;
+    TOTAL_SHARES.update(storage, coin.denom.as_str(), |total| -> StdResult<Uint128> {
+        Ok(total.unwrap_or_default().checked_add(shares)?)
+    })?;
+    Ok(())
+}
+
+fn clear_contributions(storage: &mut dyn Storage, denom: &str) -> StdResult<()> {
+    for depositor in load_depositors(storage)? {
+        if let Some(mut contribution) = CONTRIBUTIONS.may_load(storage, (depositor.as_str(), denom))? {
+            contribution.shares = Uint128::zero();
+            CONTRIBUTIONS.save(storage, (depositor.as_str(), denom), &contribution)?;
+        }
+    }
+    TOTAL_SHARES.remove(storage, denom);
+    Ok(())
+}
+
+fn load_depositors(storage: &dyn Storage) -> StdResult<Vec<String>> {
+    DEPOSITORS
+        .keys(storage, None, None, Order::Ascending)
+        .map(String::from_utf8)
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(StdError::from)
+}
+
+/// Returns the contributions of `depositor` with shares, keyed by denom.
+fn load_contributions(storage: &dyn Storage, depositor: &str) -> StdResult<Vec<(String, Contribution)>> {
+    CONTRIBUTIONS
+        .prefix(depositor)
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.and_then(|(denom, contribution)| Ok((String::from_utf8(denom).map_err(StdError::from)?, contribution))))
+        .filter(|item| item.as_ref().map_or(true, |(_, contribution)| !contribution.shares.is_zero()))
+        .collect()
+}
+
+/// Pays `depositor` its share of the funds that are not reserved, debiting the budgets and burning its shares.
+fn withdraw_excess(deps: DepsMut, contract_info: &ContractInfoResponse, depositor: &str) -> Result<Vec<Coin>, ContractError> {
+    let mut excess = load_excess(deps.as_ref(), contract_info)?;
+    let mut withdrawn = vec![];
+    for (denom, mut contribution) in load_contributions(deps.storage, depositor)? {
+        let total_excess = excess.iter()
+            .filter(|(_, coin)| coin.denom == denom)
+            .try_fold(Uint128::zero(), |sum, (_, coin)| sum.checked_add(coin.amount)).map_err(StdError::from)?;
+        let total_shares = TOTAL_SHARES.load(deps.storage, denom.as_str())?;
+        let amount = total_excess.multiply_ratio(contribution.shares, total_shares);
+        if amount.is_zero() {
+            continue;
+        }
+
+        // the shares are burned at the value of all funds, so the reserved funds stay shared pro-rata
+        let pool = load_pool(deps.storage, contract_info, denom.as_str())?;
+        let burned = amount.multiply_ratio(total_shares, pool).min(contribution.shares);
+        contribution.shares = contribution.shares.saturating_sub(burned);
+        CONTRIBUTIONS.save(deps.storage, (depositor, denom.as_str()), &contribution)?;
+        TOTAL_SHARES.save(deps.storage, denom.as_str(), &total_shares.saturating_sub(burned))?;
+
+        let mut remaining = amount;
+        for (source_contract, coin) in excess.iter_mut().filter(|(_, coin)| coin.denom == denom) {
+            let debit = Coin { denom: denom.to_string(), amount: remaining.min(coin.amount) };
+            if debit.amount.is_zero() {
+                continue;
+            }
+            debit_budget(deps.storage, source_contract.as_deref(), &debit)?;
+            coin.amount = coin.amount.saturating_sub(debit.amount);
+            remaining = remaining.saturating_sub(debit.amount);
+        }
+        withdrawn.push(Coin { denom, amount });
+    }
+    Ok(withdrawn)
+}
+
+/// Adds `coin` to `coins`, merging it with the coin of the same denom.
+fn add_coin(coins: &mut Vec<Coin>, coin: &Coin) -> StdResult<()> {
+    match coins.iter_mut().find(|c| c.denom == coin.denom) {
+        Some(c) => c.amount = c.amount.checked_add(coin.amount)?,
+        None => coins.push(coin.clone()),
+    }
+    Ok(())
+}
+
+fn clear_budgets(storage: &mut dyn Storage, contract_info: &ContractInfoResponse) -> StdResult<()> {
+    for source_contract in contract_info.source_contracts.iter() {
+        for coin in load_budget(storage, Some(source_contract.as_str()))? {
+            BUDGETS.remove(storage, (source_contract.as_str(), coin.denom.as_str()));
+        }
+    }
+    for coin in load_budget(storage, None)? {
+        UNALLOCATED.remove(storage, coin.denom.as_str());
+    }
+    Ok(())
+}
+
+pub fn execute_recover_owner(deps: DepsMut,
+                             _env: Env,
+                             _info: MessageInfo,
+                             contract: String,
+                             token_id: String) -> Result<Response, ContractError> {
+    let res = TOKEN_OWNER_INFO.load(deps.storage, (contract.to_string(), token_id.to_string()));
+    if res.is_err() {
+        return Err(ContractError::Std(res.unwrap_err()));
+    }
+    let token_owner_info = res.unwrap();
+    let sender = token_owner_info.sender;
+
+    let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft {
+        recipient: sender.to_string(),
+        token_id: token_id.to_string(),
+    };
+
+    let execute_wasm_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_binary(&transfer_msg)?,
+        send: vec![],
+    });
+
+
+    return Ok(Response {
+        submessages: vec![],
+        messages: vec![execute_wasm_msg],
+        attributes: vec![
+            attr("action", "recover_owner"),
+            attr("sender", sender.to_string()),
+            attr("sender_contract", contract.to_string()),
+            attr("token_id", token_id.to_string()),
+        ],
+        data: None,
+    });
+}
+
+pub fn execute_refund(mut deps: DepsMut,
+                      env: Env,
+                      info: MessageInfo) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let payer = contract_info.payer.to_string();
+    if info.sender.as_str().ne(payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    let refundable_at = REFUNDABLE_AT.may_load(deps.storage)?;
+    let payouts = match refundable_at {
+        Some(refundable_at) if env.block.time >= refundable_at => {
+            let balances = query_balances(deps.as_ref(), &env)?;
+            distribute(deps.storage, &contract_info, balances)?
+        }
+        _ => {
+            let withdrawn = withdraw_excess(deps.branch(), &contract_info, payer.as_str())?;
+            vec![(payer.to_string(), withdrawn)]
+        }
+    };
+
+    let mut refund = vec![];
+    let mut submessages = vec![];
+    let mut messages = vec![];
+    for (recipient, coins) in payouts {
+        let coins: Vec<Coin> = coins.into_iter().filter(|coin| !coin.amount.is_zero()).collect();
+        if coins.is_empty() {
+            continue;
+        }
+        for coin in coins.iter() {
+            add_coin(&mut refund, coin)?;
+        }
+        let (mut cw20_transfers, mut bank_sends) = refund_msgs(deps.storage, recipient.as_str(), coins)?;
+        submessages.append(&mut cw20_transfers);
+        messages.append(&mut bank_sends);
+    }
+    if refund.is_empty() {
+        return Err(ContractError::NothingToRefund {});
+    }
+
+    return Ok(Response {
+        submessages,
+        messages,
+        attributes: vec![
+            attr("action", "refund"),
+            attr("sender_contract", info.sender.to_string()),
+            attr("refund", refund.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+        ],
+        data: None,
+    });
+}
+
+/// Distributes `balances` to the outstanding claims, then to the depositors pro-rata to their shares, and the rest to the payer.
+/// Clears the claims, contributions and budgets.
+fn distribute(storage: &mut dyn Storage, contract_info: &ContractInfoResponse, mut balances: Vec<Coin>) -> StdResult<Vec<(String, Vec<Coin>)>> {
+    let mut payouts = settle_claims(storage, &mut balances)?;
+
+    // the deposited funds are shared by the depositors, and the funds sent directly to this contract go to the payer
+    let mut shared: Vec<Coin> = vec![];
+    for coin in balances.iter() {
+        let pool = load_pool(storage, contract_info, coin.denom.as_str())?;
+        shared.push(Coin { denom: coin.denom.clone(), amount: pool.min(coin.amount) });
+    }
+
+    let mut rest = balances;
+    for depositor in load_depositors(storage)? {
+        let mut coins = vec![];
+        for (denom, contribution) in load_contributions(storage, depositor.as_str())? {
+            let total_shares = TOTAL_SHARES.load(storage, denom.as_str())?;
+            let (shared, balance) = match (shared.iter().find(|c| c.denom == denom), rest.iter_mut().find(|c| c.denom == denom)) {
+                (Some(shared), Some(balance)) => (shared, balance),
+                _ => continue,
+            };
+            let amount = shared.amount.multiply_ratio(contribution.shares, total_shares).min(balance.amount);
+            balance.amount = balance.amount.saturating_sub(amount);
+            coins.push(Coin { denom, amount });
+        }
+        match payouts.iter_mut().find(|(recipient, _)| recipient == &depositor) {
+            Some((_, paid)) => for coin in coins.iter() {
+                add_coin(paid, coin)?;
+            },
+            None => payouts.push((depositor, coins)),
+        }
+    }
+
+    match payouts.iter_mut().find(|(recipient, _)| recipient == &contract_info.payer) {
+        Some((_, coins)) => for coin in rest.iter() {
+            add_coin(coins, coin)?;
+        },
+        None => payouts.push((contract_info.payer.to_string(), rest)),
+    }
+
+    for depositor in load_depositors(storage)? {
+        for (denom, _) in load_contributions(storage, depositor.as_str())? {
+            clear_contributions(storage, denom.as_str())?;
+        }
+    }
+    clear_budgets(storage, contract_info)?;
+    Ok(payouts)
+}
+
+/// Pays the outstanding claims from `balances` in order of redemption, up to the budgets they are queued on.
+/// Clears the claims, so the unpaid rest of the claims is forfeited.
+fn settle_claims(storage: &mut dyn Storage, balances: &mut [Coin]) -> StdResult<Vec<(String, Vec<Coin>)>> {
+    let claims = CLAIMS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, claim)| claim))
+        .collect::<StdResult<Vec<Claim>>>()?;
+
+    let mut payouts: Vec<(String, Vec<Coin>)> = vec![];
+    for claim in claims {
+        let source_contract = claim.source_contract.as_str();
+        let denom = claim.outstanding.denom.as_str();
+        remove_claim(storage, &claim);
+        OUTSTANDING.remove(storage, (source_contract, denom));
+
+        let (mut budget, balance) = match (BUDGETS.may_load(storage, (source_contract, denom))?, balances.iter_mut().find(|c| c.denom == denom)) {
+            (Some(budget), Some(balance)) => (budget, balance),
+            _ => continue,
+        };
+        let payment = Coin { denom: denom.to_string(), amount: claim.outstanding.amount.min(budget.amount).min(balance.amount) };
+        if payment.amount.is_zero() {
+            continue;
+        }
+        budget.amount = budget.amount.saturating_sub(payment.amount);
+        BUDGETS.save(storage, (source_contract, denom), &budget)?;
+        balance.amount = balance.amount.saturating_sub(payment.amount);
+
+        match payouts.iter_mut().find(|(recipient, _)| recipient == &claim.sender) {
+            Some((_, coins)) => add_coin(coins, &payment)?,
+            None => payouts.push((claim.sender.to_string(), vec![payment])),
+        }
+    }
+    Ok(payouts)
+}
+
+/// Saves `claim` with its entries in the queue of its budget and in the claims of its sender.
+fn save_claim(storage: &mut dyn Storage, claim: &Claim) -> StdResult<()> {
+    CLAIMS.save(storage, U64Key::new(claim.id), claim)?;
+    CLAIM_QUEUES.save(storage, ((claim.source_contract.as_str(), claim.outstanding.denom.as_str()), U64Key::new(claim.id)), &claim.outstanding.amount)?;
+    SENDER_CLAIMS.save(storage, (claim.sender.as_str(), U64Key::new(claim.id)), &Empty {})
+}
+
+fn remove_claim(storage: &mut dyn Storage, claim: &Claim) {
+    CLAIMS.remove(storage, U64Key::new(claim.id));
+    CLAIM_QUEUES.remove(storage, ((claim.source_contract.as_str(), claim.outstanding.denom.as_str()), U64Key::new(claim.id)));
+    SENDER_CLAIMS.remove(storage, (claim.sender.as_str(), U64Key::new(claim.id)));
+}
+
+/// Returns the outstanding amount of the claims queued on the budget of `claim` before it.
+fn load_claims_ahead(storage: &dyn Storage, claim: &Claim) -> StdResult<Uint128> {
+    CLAIM_QUEUES
+        .prefix((claim.source_contract.as_str(), claim.outstanding.denom.as_str()))
+        .range(storage, None, Some(Bound::exclusive_int(claim.id)), Order::Ascending)
+        .try_fold(Uint128::zero(), |ahead, item| item.map(|(_, outstanding)| ahead + outstanding))
+}
+
+fn parse_claim_id(key: Vec<u8>) -> StdResult<u64> {
+    let bytes: [u8; 8] = key.as_slice().try_into().map_err(|_| StdError::generic_err("invalid claim id"))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+pub fn execute_withdraw(mut deps: DepsMut,
+                        _env: Env,
+                        info: MessageInfo) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let withdrawn: Vec<Coin> = withdraw_excess(deps.branch(), &contract_info, info.sender.as_str())?;
+    if withdrawn.is_empty() {
+        return Err(ContractError::NothingToRefund {});
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: send_msgs(deps.storage, info.sender.as_str(), withdrawn.clone())?,
+        attributes: vec![
+            attr("action", "withdraw"),
+            attr("sender", info.sender.to_string()),
+            attr("amount", withdrawn.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_wind_down(deps: DepsMut,
+                         env: Env,
+                         info: MessageInfo) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    if REFUNDABLE_AT.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::InvalidParameter { msg: "already winding down.".to_string() });
+    }
+
+    let refundable_at = env.block.time.plus_seconds(contract_info.wind_down_grace_seconds);
+    REFUNDABLE_AT.save(deps.storage, &refundable_at)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "wind_down"),
+            attr("refundable_at", refundable_at.seconds()),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_cancel_wind_down(deps: DepsMut,
+                                _env: Env,
+                                info: MessageInfo) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    if REFUNDABLE_AT.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::InvalidParameter { msg: "not winding down.".to_string() });
+    }
+
+    REFUNDABLE_AT.remove(deps.storage);
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "cancel_wind_down"),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_claim_outstanding(deps: DepsMut,
+                                 env: Env,
+                                 info: MessageInfo) -> Result<Response, ContractError> {
+    if let Some(paused) = PAUSED.may_load(deps.storage)? {
+        return Err(ContractError::Paused { reason: paused.reason });
+    }
+
+    let claim_ids = SENDER_CLAIMS
+        .prefix(info.sender.as_str())
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(parse_claim_id)
+        .collect::<StdResult<Vec<u64>>>()?;
+    if claim_ids.is_empty() {
+        return Err(ContractError::NoOutstandingClaims {});
+    }
+
+    let mut paid = vec![];
+    let mut attributes = vec![
+        attr("action", "claim_outstanding"),
+        attr("sender", info.sender.to_string()),
+    ];
+
+    for claim_id in claim_ids {
+        let mut claim = CLAIMS.load(deps.storage, U64Key::new(claim_id))?;
+        let denom = claim.outstanding.denom.clone();
+        // the earlier claims on the same budget are paid first
+        let ahead = load_claims_ahead(deps.storage, &claim)?;
+        let budget = BUDGETS.may_load(deps.storage, (claim.source_contract.as_str(), denom.as_str()))?
+            .map_or_else(Uint128::zero, |budget| budget.amount);
+        let payment = Coin { denom: denom.clone(), amount: budget.saturating_sub(ahead).min(claim.outstanding.amount) };
+        if payment.amount.is_zero() {
+            continue;
+        }
+
+        if let Some(paused) = check_circuit_breaker(deps.storage, &env, &payment)? {
+            attributes.push(attr("paused", paused.reason));
+            break;
+        }
+        // the claims over the rate limits are left for later
+        match check_rate_limits(deps.storage, &env, info.sender.as_str(), &payment, false) {
+            Err(ContractError::RateLimitExceeded { msg }) if !paid.is_empty() => {
+                attributes.push(attr("rate_limited", msg));
+                break;
+            }
+            res => res?,
+        }
+        record_circuit_breaker(deps.storage, &env, &payment)?;
+        record_rate_limits(deps.storage, &env, info.sender.as_str(), &payment, false)?;
+
+        debit_budget(deps.storage, Some(claim.source_contract.as_str()), &payment)?;
+        OUTSTANDING.update(deps.storage, (claim.source_contract.as_str(), denom.as_str()), |queued| -> StdResult<Coin> {
+            let mut queued = queued.unwrap_or_else(|| Coin::new(0, denom.clone()));
+            queued.amount = queued.amount.saturating_sub(payment.amount);
+            Ok(queued)
+        })?;
+        claim.outstanding.amount = claim.outstanding.amount.saturating_sub(payment.amount);
+        if claim.outstanding.amount.is_zero() {
+            remove_claim(deps.storage, &claim);
+        } else {
+            save_claim(deps.storage, &claim)?;
+        }
+        attributes.push(attr("claim_id", claim.id));
+        add_coin(&mut paid, &payment)?;
+    }
+
+    if paid.is_empty() {
+        // the pause of the circuit breaker is kept
+        if PAUSED.may_load(deps.storage)?.is_some() {
+            return Ok(Response { submessages: vec![], messages: vec![], attributes, data: None });
+        }
+        return Err(ContractError::InsufficientDeposit {});
+    }
+    attributes.push(attr("paid", paid.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+
+    let mut messages = vec![];
+    match FEE_CONFIG.may_load(deps.storage)? {
+        Some(fee_config) => {
+            // the flat fee is taken once, when the token is redeemed
+            let fees: Vec<Coin> = paid.iter().map(|coin| calculate_fee(&fee_config, coin, false)).collect();
+            let net: Vec<Coin> = paid.iter().zip(fees.iter())
+                .map(|(coin, fee)| Coin { denom: coin.denom.clone(), amount: coin.amount.saturating_sub(fee.amount) })
+                .filter(|coin| !coin.amount.is_zero())
+                .collect();
+            let fees: Vec<Coin> = fees.into_iter().filter(|fee| !fee.amount.is_zero()).collect();
+
+            attributes.push(attr("fee", fees.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+            if !net.is_empty() {
+                messages.append(&mut send_msgs(deps.storage, info.sender.as_str(), net)?);
+            }
+            if !fees.is_empty() {
+                messages.append(&mut send_msgs(deps.storage, fee_config.treasury.as_str(), fees)?);
+            }
+        }
+        None => messages.append(&mut send_msgs(deps.storage, info.sender.as_str(), paid)?),
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages,
+        attributes,
+        data: None,
+    })
+}
+
+pub fn execute_set_conversion(deps: DepsMut,
+                              _env: Env,
+                              info: MessageInfo,
+                              conversion: Conversion) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    if conversion.from_denom == conversion.to_denom {
+        return Err(ContractError::InvalidParameter { msg: "from_denom and to_denom must be different.".to_string() });
+    }
+    if conversion.rate.map_or(false, |rate| rate.is_zero()) {
+        return Err(ContractError::InvalidParameter { msg: "rate must be greater than 0.".to_string() });
+    }
+    if let (Some(min_rate), Some(max_rate)) = (conversion.min_rate, conversion.max_rate) {
+        if min_rate > max_rate {
+            return Err(ContractError::InvalidParameter { msg: "min_rate must not be greater than max_rate.".to_string() });
+        }
+    }
+
+    CONVERSIONS.save(deps.storage, conversion.from_denom.as_str(), &conversion)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "set_conversion"),
+            attr("from_denom", conversion.from_denom),
+            attr("to_denom", conversion.to_denom),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_remove_conversion(deps: DepsMut,
+                                 _env: Env,
+                                 info: MessageInfo,
+                                 from_denom: String) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    CONVERSIONS.remove(deps.storage, from_denom.as_str());
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "remove_conversion"),
+            attr("from_denom", from_denom),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_set_oracle(deps: DepsMut,
+                          _env: Env,
+                          info: MessageInfo,
+                          oracle: Option<String>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    match oracle.as_ref() {
+        Some(oracle) => ORACLE.save(deps.storage, &deps.api.addr_validate(oracle)?.to_string())?,
+        None => ORACLE.remove(deps.storage),
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "set_oracle"),
+            attr("oracle", oracle.unwrap_or_default()),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_update_fee_config(deps: DepsMut,
+                                 _env: Env,
+                                 info: MessageInfo,
+                                 fee_config: Option<FeeConfig>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    let mut attributes = vec![attr("action", "update_fee_config")];
+    match fee_config {
+        Some(mut fee_config) => {
+            if fee_config.percent > Decimal::one() {
+                return Err(ContractError::InvalidParameter { msg: "percent must not be greater than 1.".to_string() });
+            }
+            fee_config.treasury = deps.api.addr_validate(fee_config.treasury.as_str())?.to_string();
+            FEE_CONFIG.save(deps.storage, &fee_config)?;
+
+            attributes.push(attr("treasury", fee_config.treasury));
+            attributes.push(attr("percent", fee_config.percent));
+            attributes.push(attr("flat", fee_config.flat.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+        }
+        None => FEE_CONFIG.remove(deps.storage),
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes,
+        data: None,
+    })
+}
+
+pub fn execute_update_rate_limits(deps: DepsMut,
+                                  _env: Env,
+                                  info: MessageInfo,
+                                  rate_limits: Option<RateLimits>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    let mut attributes = vec![attr("action", "update_rate_limits")];
+    match rate_limits {
+        Some(rate_limits) => {
+            if !rate_limits.max_payout_per_window.is_empty() && rate_limits.window_seconds == 0 {
+                return Err(ContractError::InvalidParameter { msg: "window_seconds must be greater than 0.".to_string() });
+            }
+            RATE_LIMITS.save(deps.storage, &rate_limits)?;
+
+            if let Some(max_redemptions) = rate_limits.max_redemptions_per_day {
+                attributes.push(attr("max_redemptions_per_day", max_redemptions));
+            }
+            attributes.push(attr("max_payout_per_window", rate_limits.max_payout_per_window.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+            attributes.push(attr("window_seconds", rate_limits.window_seconds));
+            attributes.push(attr("max_payout_per_block", rate_limits.max_payout_per_block.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+        }
+        None => RATE_LIMITS.remove(deps.storage),
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes,
+        data: None,
+    })
+}
+
+pub fn execute_set_redeem_window(deps: DepsMut,
+                                 _env: Env,
+                                 info: MessageInfo,
+                                 source_contract: Option<String>,
+                                 redeem_window: RedeemWindow) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    validate_redeem_window(&redeem_window)?;
+
+    let mut attributes = vec![attr("action", "set_redeem_window")];
+    match source_contract {
+        Some(source_contract) => {
+            if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
+                return Err(ContractError::InvalidParameter { msg: format!("unexpected source contract. actual: {}, expected: {}", source_contract, contract_info.source_contracts.join(",")) });
+            }
+            REDEEM_WINDOWS.save(deps.storage, source_contract.as_str(), &redeem_window)?;
+            attributes.push(attr("source_contract", source_contract));
+        }
+        None => {
+            contract_info.redeem_start = redeem_window.redeem_start;
+            contract_info.redeem_end = redeem_window.redeem_end;
+            CONTRACT_INFO.save(deps.storage, &contract_info)?;
+        }
+    }
+    if let Some(redeem_start) = redeem_window.redeem_start {
+        attributes.push(attr("redeem_start", redeem_start));
+    }
+    if let Some(redeem_end) = redeem_window.redeem_end {
+        attributes.push(attr("redeem_end", redeem_end));
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes,
+        data: None,
+    })
+}
+
+pub fn execute_set_receipt_contract(deps: DepsMut,
+                                    _env: Env,
+                                    info: MessageInfo,
+                                    receipt_contract: Option<String>) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    let receipt_contract = match receipt_contract {
+        Some(receipt_contract) => Some(deps.api.addr_validate(receipt_contract.as_str())?.to_string()),
+        None => None,
+    };
+    validate_receipt_contract(&contract_info, receipt_contract.as_ref())?;
+
+    contract_info.receipt_contract = receipt_contract.clone();
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "set_receipt_contract"),
+            attr("receipt_contract", receipt_contract.unwrap_or_default()),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_set_accepted_denoms(deps: DepsMut,
+                                   _env: Env,
+                                   info: MessageInfo,
+                                   accepted_denoms: Option<Vec<String>>) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    contract_info.accepted_denoms = accepted_denoms.clone();
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "set_accepted_denoms"),
+            attr("accepted_denoms", accepted_denoms.map_or_else(String::new, |denoms| denoms.join(", "))),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_register_cw20_tokens(deps: DepsMut,
+                                    _env: Env,
+                                    info: MessageInfo,
+                                    cw20_tokens: Vec<String>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    if cw20_tokens.is_empty() {
+        return Err(ContractError::InvalidParameter { msg: "cw20_tokens is empty.".to_string() });
+    }
+
+    for cw20_token in cw20_tokens.iter() {
+        let cw20_token = deps.api.addr_validate(cw20_token.as_str())?;
+        CW20_TOKENS.save(deps.storage, cw20_token.as_str(), &Empty {})?;
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "register_cw20_tokens"),
+            attr("cw20_tokens", cw20_tokens.join(", ")),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_sweep(deps: DepsMut,
+                     env: Env,
+                     info: MessageInfo,
+                     cw20_tokens: Option<Vec<String>>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    for cw20_token in cw20_tokens.unwrap_or_default() {
+        let cw20_token = deps.api.addr_validate(cw20_token.as_str())?;
+        CW20_TOKENS.save(deps.storage, cw20_token.as_str(), &Empty {})?;
+    }
+
+    let mut swept = vec![];
+    for balance in query_balances(deps.as_ref(), &env)? {
+        let pool = load_pool(deps.storage, &contract_info, balance.denom.as_str())?;
+        let amount = balance.amount.saturating_sub(pool);
+        if !amount.is_zero() {
+            swept.push(Coin { denom: balance.denom, amount });
+        }
+    }
+    if swept.is_empty() {
+        return Err(ContractError::NothingToRefund {});
+    }
+
+    let (submessages, messages) = refund_msgs(deps.storage, contract_info.payer.as_str(), swept.clone())?;
+    Ok(Response {
+        submessages,
+        messages,
+        attributes: vec![
+            attr("action", "sweep"),
+            attr("amount", swept.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_register_issuer(deps: DepsMut,
+                               _env: Env,
+                               info: MessageInfo,
+                               issuer: String,
+                               public_key: Binary) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    // compressed or uncompressed public key
+    if public_key.len() != 33 && public_key.len() != 65 {
+        return Err(ContractError::InvalidParameter { msg: "public_key must be a secp256k1 public key.".to_string() });
+    }
+
+    let issuer = deps.api.addr_validate(issuer.as_str())?.to_string();
+    ISSUERS.save(deps.storage, issuer.as_str(), &public_key)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "register_issuer"),
+            attr("issuer", issuer),
+            attr("public_key", public_key.to_base64()),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_remove_issuer(deps: DepsMut,
+                             _env: Env,
+                             info: MessageInfo,
+                             issuer: String) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    ISSUERS.remove(deps.storage, issuer.as_str());
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "remove_issuer"),
+            attr("issuer", issuer),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_redeem_voucher(deps: DepsMut,
+                              env: Env,
+                              info: MessageInfo,
+                              voucher: Voucher,
+                              signature: Binary) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if let Some(paused) = PAUSED.may_load(deps.storage)? {
+        return Err(ContractError::Paused { reason: paused.reason });
+    }
+    // the vouchers are paid from the unallocated funds, so the window of the contract applies
+    let redeem_window = RedeemWindow { redeem_start: contract_info.redeem_start, redeem_end: contract_info.redeem_end };
+    if !redeem_window.contains(env.block.time) {
+        return Err(ContractError::OutsideRedeemWindow { time: env.block.time });
+    }
+    if voucher.contract.ne(env.contract.address.as_str()) {
+        return Err(ContractError::InvalidParameter { msg: "the voucher is for another contract.".to_string() });
+    }
+    if env.block.time >= voucher.expiry {
+        return Err(ContractError::VoucherExpired {});
+    }
+    if voucher.amount.amount.is_zero() {
+        return Err(ContractError::InvalidParameter { msg: "amount is empty.".to_string() });
+    }
+    let recipient = deps.api.addr_validate(voucher.recipient.as_str())?;
+
+    let public_key = ISSUERS.may_load(deps.storage, voucher.issuer.as_str())?
+        .ok_or_else(|| ContractError::Unauthorized { msg: format!("unknown issuer. {}", voucher.issuer) })?;
+    let hash = Sha256::digest(&to_vec(&voucher)?);
+    if !deps.api.secp256k1_verify(&hash, signature.as_slice(), public_key.as_slice())? {
+        return Err(ContractError::InvalidSignature {});
+    }
+
+    let nonce_key = (voucher.issuer.as_str(), U64Key::new(voucher.nonce));
+    if USED_NONCES.may_load(deps.storage, nonce_key.clone())?.is_some() {
+        return Err(ContractError::NonceUsed { nonce: voucher.nonce });
+    }
+
+    if let Some(paused) = check_circuit_breaker(deps.storage, &env, &voucher.amount)? {
+        // the nonce and the rate limits are not used, so the voucher can be redeemed after the pause
+        return Ok(Response {
+            submessages: vec![],
+            messages: vec![],
+            attributes: vec![
+                attr("action", "redeem_voucher"),
+                attr("paused", paused.reason),
+            ],
+            data: None,
+        });
+    }
+    check_rate_limits(deps.storage, &env, recipient.as_str(), &voucher.amount, true)?;
+    record_circuit_breaker(deps.storage, &env, &voucher.amount)?;
+    record_rate_limits(deps.storage, &env, recipient.as_str(), &voucher.amount, true)?;
+    USED_NONCES.save(deps.storage, nonce_key, &Empty {})?;
+
+    debit_budget(deps.storage, None, &voucher.amount)?;
+
+    let fee_config = FEE_CONFIG.may_load(deps.storage)?;
+    let fee = fee_config.as_ref().map(|fee_config| calculate_fee(fee_config, &voucher.amount, true));
+    let net = Coin { denom: voucher.amount.denom.clone(), amount: voucher.amount.amount.saturating_sub(fee.as_ref().map_or_else(Uint128::zero, |fee| fee.amount)) };
+
+    let mut messages = vec![];
+    if !net.amount.is_zero() {
+        messages.append(&mut send_msgs(deps.storage, recipient.as_str(), vec![net])?);
+    }
+    let mut attributes = vec![
+        attr("action", "redeem_voucher"),
+        attr("sender", info.sender.to_string()),
+        attr("issuer", voucher.issuer.to_string()),
+        attr("recipient", recipient.to_string()),
+        attr("nonce", voucher.nonce),
+        attr("amount", voucher.amount),
+    ];
+    if let (Some(fee_config), Some(fee)) = (fee_config, fee) {
+        if !fee.amount.is_zero() {
+            messages.append(&mut send_msgs(deps.storage, fee_config.treasury.as_str(), vec![fee.clone()])?);
+        }
+        attributes.push(attr("fee", fee));
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages,
+        attributes,
+        data: None,
+    })
+}
+
+pub fn execute_set_price_ceilings(deps: DepsMut,
+                                  _env: Env,
+                                  info: MessageInfo,
+                                  source_contract: String,
+                                  ceilings: Vec<Coin>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+    if is_invalid_from_contract(&contract_info, source_contract.to_string()) {
+        return Err(ContractError::InvalidParameter { msg: format!("unexpected source contract. actual: {}, expected: {}", source_contract, contract_info.source_contracts.join(",")) });
+    }
+
+    PRICE_CEILINGS.save(deps.storage, source_contract.as_str(), &ceilings)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "set_price_ceilings"),
+            attr("source_contract", source_contract),
+            attr("ceilings", ceilings.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_pause(deps: DepsMut,
+                     env: Env,
+                     info: MessageInfo) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    let paused = PauseInfo { reason: "paused by the payer".to_string(), paused_at: env.block.time };
+    PAUSED.save(deps.storage, &paused)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "pause"),
+            attr("reason", paused.reason),
+        ],
+        data: None,
+    })
+}
+
+pub fn execute_unpause(deps: DepsMut,
+                       _env: Env,
+                       info: MessageInfo) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    PAUSED.remove(deps.storage);
+    // the payouts before the pause must not trip the circuit breaker again
+    clear_payout_slots(deps.storage);
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![attr("action", "unpause")],
+        data: None,
+    })
+}
+
+pub fn execute_update_circuit_breaker(deps: DepsMut,
+                                      _env: Env,
+                                      info: MessageInfo,
+                                      circuit_breaker: Option<CircuitBreaker>) -> Result<Response, ContractError> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    if info.sender.as_str().ne(contract_info.payer.as_str()) {
+        return Err(ContractError::UnmatchedPayer {});
+    }
+
+    let mut attributes = vec![attr("action", "update_circuit_breaker")];
+    match circuit_breaker {
+        Some(circuit_breaker) => {
+            if circuit_breaker.window_seconds == 0 {
+                return Err(ContractError::InvalidParameter { msg: "window_seconds must be greater than 0.".to_string() });
+            }
+            // the slots of the payouts depend on the window
+            if CIRCUIT_BREAKER.may_load(deps.storage)?.map_or(true, |current| current.window_seconds != circuit_breaker.window_seconds) {
+                clear_payout_slots(deps.storage);
+            }
+            CIRCUIT_BREAKER.save(deps.storage, &circuit_breaker)?;
+
+            attributes.push(attr("threshold", circuit_breaker.threshold.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")));
+            attributes.push(attr("window_seconds", circuit_breaker.window_seconds));
+        }
+        None => {
+            CIRCUIT_BREAKER.remove(deps.storage);
+            clear_payout_slots(deps.storage);
+        }
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes,
+        data: None,
+    })
+}
+
+/// Ignores the failed CW20 transfers of the refunds. The tokens stay in this contract.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if msg.id != CW20_REFUND_REPLY_ID {
+        return Err(ContractError::InvalidParameter { msg: format!("unknown reply id: {}", msg.id) });
+    }
+
+    let error = match msg.result {
+        ContractResult::Ok(_) => String::new(),
+        ContractResult::Err(error) => error,
+    };
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "refund_cw20_failed"),
+            attr("error", error),
+        ],
+        data: None,
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::ContractInfo {} => to_binary(&query_contract_info(deps)?),
+        QueryMsg::Budget { source_contract } => to_binary(&query_budget(deps, source_contract)?),
+        QueryMsg::Unallocated {} => to_binary(&query_unallocated(deps)?),
+        QueryMsg::Reserves {} => to_binary(&query_reserves(deps)?),
+        QueryMsg::Claims { sender, start_after, limit } => to_binary(&query_claims(deps, sender, start_after, limit)?),
+        QueryMsg::Conversion { from_denom } => to_binary(&query_conversion(deps, from_denom)?),
+        QueryMsg::FeeConfig {} => to_binary(&query_fee_config(deps)?),
+        QueryMsg::Limits { sender } => to_binary(&query_limits(deps, env, sender)?),
+        QueryMsg::RedeemWindow { source_contract } => to_binary(&query_redeem_window(deps, source_contract)?),
+        QueryMsg::Status {} => to_binary(&query_status(deps, env)?),
+        QueryMsg::Depositors {} => to_binary(&query_depositors(deps)?),
+        QueryMsg::Issuers {} => to_binary(&query_issuers(deps)?),
+        QueryMsg::PriceCeilings { source_contract } => to_binary(&query_price_ceilings(deps, source_contract)?),
+    }
+}
+
+fn query_contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
+    CONTRACT_INFO.load(deps.storage)
+}
+
+fn query_budget(deps: Deps, source_contract: String) -> StdResult<BudgetResponse> {
+    let amount = load_budget(deps.storage, Some(source_contract.as_str()))?;
+    Ok(BudgetResponse { source_contract, amount })
+}
+
+fn query_unallocated(deps: Deps) -> StdResult<UnallocatedResponse> {
+    let amount = load_budget(deps.storage, None)?;
+    Ok(UnallocatedResponse { amount })
+}
+
+fn query_claims(deps: Deps, sender: String, start_after: Option<u64>, limit: Option<u32>) -> StdResult<ClaimsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let claims = SENDER_CLAIMS
+        .prefix(sender.as_str())
+        .keys(deps.storage, start_after.map(Bound::exclusive_int), None, Order::Ascending)
+        .take(limit)
+        .map(|key| parse_claim_id(key).and_then(|claim_id| CLAIMS.load(deps.storage, U64Key::new(claim_id))))
+        .collect::<StdResult<Vec<Claim>>>()?;
+    Ok(ClaimsResponse { claims })
+}
+
+fn query_conversion(deps: Deps, from_denom: String) -> StdResult<ConversionResponse> {
+    let conversion = CONVERSIONS.load(deps.storage, from_denom.as_str())?;
+    let rate = exchange_rate(deps, &conversion).map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok(ConversionResponse {
+        conversion,
+        oracle: ORACLE.may_load(deps.storage)?,
+        rate,
+    })
+}
+
+fn query_fee_config(deps: Deps) -> StdResult<FeeConfigResponse> {
+    Ok(FeeConfigResponse { fee_config: FEE_CONFIG.may_load(deps.storage)? })
+}
+
+fn query_limits(deps: Deps, env: Env, sender: String) -> StdResult<LimitsResponse> {
+    let rate_limits = match RATE_LIMITS.may_load(deps.storage)? {
+        Some(rate_limits) => rate_limits,
+        None => return Ok(LimitsResponse { rate_limits: None, remaining_redemptions: None, remaining_payout: vec![], remaining_block_payout: vec![] }),
+    };
+
+    let usage = load_rate_usage(deps.storage, &env, sender.as_str(), &rate_limits)?;
+    let remaining_redemptions = rate_limits.max_redemptions_per_day
+        .map(|max_redemptions| max_redemptions.saturating_sub(usage.redemptions));
+    let remaining_payout = rate_limits.max_payout_per_window.iter().map(|max_payout| {
+        let payout = usage.payout.iter().find(|c| c.denom == max_payout.denom).map_or_else(Uint128::zero, |c| c.amount);
+        Coin { denom: max_payout.denom.clone(), amount: max_payout.amount.saturating_sub(payout) }
+    }).collect();
+
+    let block_payout = load_block_payout(deps.storage, &env)?;
+    let remaining_block_payout = rate_limits.max_payout_per_block.iter().map(|max_payout| {
+        let payout = block_payout.amount.iter().find(|c| c.denom == max_payout.denom).map_or_else(Uint128::zero, |c| c.amount);
+        Coin { denom: max_payout.denom.clone(), amount: max_payout.amount.saturating_sub(payout) }
+    }).collect();
+
+    Ok(LimitsResponse {
+        rate_limits: Some(rate_limits),
+        remaining_redemptions,
+        remaining_payout,
+        remaining_block_payout,
+    })
+}
+
+fn query_redeem_window(deps: Deps, source_contract: String) -> StdResult<RedeemWindowResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let redeem_window = load_redeem_window(deps.storage, &contract_info, source_contract.as_str())?;
+    Ok(RedeemWindowResponse { source_contract, redeem_window })
+}
+
+fn query_status(deps: Deps, env: Env) -> StdResult<StatusResponse> {
+    let circuit_breaker = CIRCUIT_BREAKER.may_load(deps.storage)?;
+    let window_payout = match circuit_breaker.as_ref() {
+        Some(circuit_breaker) => load_window_payout(deps.storage, &env, circuit_breaker)?,
+        None => vec![],
+    };
+
+    Ok(StatusResponse {
+        paused: PAUSED.may_load(deps.storage)?,
+        circuit_breaker,
+        window_payout,
+    })
+}
+
+fn query_depositors(deps: Deps) -> StdResult<DepositorsResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let excess = load_excess(deps, &contract_info)?;
+
+    let mut depositors = vec![];
+    for depositor in load_depositors(deps.storage)? {
+        let mut contributions = vec![];
+        for item in CONTRIBUTIONS.prefix(depositor.as_str()).range(deps.storage, None, None, Order::Ascending) {
+            let (denom, contribution) = item?;
+            let denom = String::from_utf8(denom).map_err(StdError::from)?;
+            let total_shares = TOTAL_SHARES.may_load(deps.storage, denom.as_str())?.unwrap_or_default();
+            let (value, withdrawable) = if total_shares.is_zero() {
+                (Uint128::zero(), Uint128::zero())
+            } else {
+                let pool = load_pool(deps.storage, &contract_info, denom.as_str())?;
+                let total_excess = excess.iter()
+                    .filter(|(_, coin)| coin.denom == denom)
+                    .try_fold(Uint128::zero(), |sum, (_, coin)| sum.checked_add(coin.amount))?;
+                (pool.multiply_ratio(contribution.shares, total_shares), total_excess.multiply_ratio(contribution.shares, total_shares))
+            };
+            contributions.push(ContributionInfo {
+                denom,
+                deposited: contribution.deposited,
+                shares: contribution.shares,
+                value,
+                withdrawable,
+            });
+        }
+        depositors.push(DepositorInfo { depositor, contributions });
+    }
+    Ok(DepositorsResponse { depositors })
+}
+
+fn query_issuers(deps: Deps) -> StdResult<IssuersResponse> {
+    let issuers = ISSUERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.and_then(|(issuer, public_key)| Ok(IssuerInfo { issuer: String::from_utf8(issuer).map_err(StdError::from)?, public_key })))
+        .collect::<StdResult<Vec<IssuerInfo>>>()?;
+    Ok(IssuersResponse { issuers })
+}
+
+fn query_price_ceilings(deps: Deps, source_contract: String) -> StdResult<PriceCeilingsResponse> {
+    Ok(PriceCeilingsResponse {
+        ceilings: PRICE_CEILINGS.may_load(deps.storage, source_contract.as_str())?.unwrap_or_default(),
+        price_caps: query_price_caps(deps, source_contract.as_str()),
+        source_contract,
+    })
+}
+
+fn query_reserves(deps: Deps) -> StdResult<ReservesResponse> {
+    let contract_info = CONTRACT_INFO.load(deps.storage)?;
+
+    let mut liabilities = vec![];
+    for source_contract in contract_info.source_contracts.iter() {
+        let liability = match load_liability(deps, source_contract)? {
+            Some(liability) => liability,
+            None => load_budget(deps.storage, Some(source_contract.as_str()))?,
+        };
+        for coin in liability.iter() {
+            add_coin(&mut liabilities, coin)?;
+        }
+    }
+
+    let mut excess = vec![];
+    for (_, coin) in load_excess(deps, &contract_info)?.iter() {
+        add_coin(&mut excess, coin)?;
+    }
+
+    Ok(ReservesResponse {
+        liabilities,
+        excess,
+        refundable_at: REFUNDABLE_AT.may_load(deps.storage)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use cosmwasm_std::{coin, ContractResult, Empty, from_slice, OwnedDeps, Querier, QuerierResult, QueryRequest, SystemError, SystemResult, to_vec, WasmQuery};
+    use cosmwasm_std::testing::{mock_dependencies, MOCK_CONTRACT_ADDR, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_vm::testing::{mock_env, mock_info};
+    use cw721::NftInfoResponse;
+
+    use crate::msg::TokenInfoMsg;
+    use crate::types::Asset;
+
+    use super::*;
+
+    /// A querier that answers the `AllNftInfo` queries of the source contracts,
+    /// which `MockQuerier` does not support yet.
+    struct NftQuerier {
+        base: MockQuerier,
+        nfts: HashMap<(String, String), AllNftInfoResponse>,
+        supplies: HashMap<String, Vec<Coin>>,
+        uninitialized_supplies: HashSet<String>,
+        cw20_balances: HashMap<String, Uint128>,
+        exchange_rates: HashMap<(String, String), Decimal>,
+        price_caps: HashMap<String, Vec<Coin>>,
+    }
+
+    impl NftQuerier {
+        fn add_nft(&mut self, contract: &str, token_id: &str, owner: &str, price: impl Into<Asset>) {
+            let all_nft_info = AllNftInfoResponse {
+                access: OwnerOfResponse {
+                    owner: owner.to_string(),
+                    approvals: vec![],
+                },
+                info: NftInfoResponse {
+                    name: token_id.to_string(),
+                    description: String::from_utf8(to_vec(&TokenInfo { price: price.into() }).unwrap()).unwrap(),
+                    image: None,
+                },
+            };
+            self.nfts.insert((contract.to_string(), token_id.to_string()), all_nft_info);
+        }
+
+        fn handle_wasm(&self, contract_addr: &str, msg: &Binary) -> QuerierResult {
+            let result = if let Ok(SourceQueryMsg::FaceValueSupply {}) = from_binary(msg) {
+                match self.supplies.get(contract_addr) {
+                    Some(amount) => to_binary(&FaceValueSupplyResponse { amount: amount.clone(), initialized: !self.uninitialized_supplies.contains(contract_addr) }),
+                    None => return SystemResult::Err(SystemError::UnsupportedRequest { kind: "face_value_supply".to_string() }),
+                }
+            } else if let Ok(SourceQueryMsg::MintPolicy {}) = from_binary(msg) {
+                match self.price_caps.get(contract_addr) {
+                    Some(price_caps) => to_binary(&MintPolicyResponse { minter: "minter".to_string(), price_caps: price_caps.clone() }),
+                    None => return SystemResult::Err(SystemError::UnsupportedRequest { kind: "mint_policy".to_string() }),
+                }
+            } else if let Ok(OracleQueryMsg::ExchangeRate { base_denom, quote_denom }) = from_binary(msg) {
+                match self.exchange_rates.get(&(base_denom, quote_denom)) {
+                    Some(rate) => to_binary(&ExchangeRateResponse { rate: *rate }),
+                    None => return SystemResult::Ok(ContractResult::Err("exchange rate not found".to_string())),
+                }
+            } else if let Ok(Cw20QueryMsg::Balance { address }) = from_binary(msg) {
+                assert_eq!(MOCK_CONTRACT_ADDR, address);
+                match self.cw20_balances.get(contract_addr) {
+                    Some(balance) => to_binary(&BalanceResponse { balance: *balance }),
+                    None => return SystemResult::Err(SystemError::NoSuchContract { addr: contract_addr.to_string() }),
+                }
+            } else if let Ok(cw721_base::msg::QueryMsg::AllNftInfo { token_id, .. }) = from_binary(msg) {
+                match self.nfts.get(&(contract_addr.to_string(), token_id)) {
+                    Some(all_nft_info) => to_binary(all_nft_info),
+                    None => return SystemResult::Ok(ContractResult::Err("token not found".to_string())),
+                }
+            } else {
+                return SystemResult::Err(SystemError::UnsupportedRequest { kind: "wasm".to_string() });
+            };
+            SystemResult::Ok(ContractResult::Ok(result.unwrap()))
+        }
+    }
+
+    impl Querier for NftQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            match &request {
+                QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => self.handle_wasm(contract_addr, msg),
+                _ => self.base.handle_query(&request),
+            }
+        }
+    }
+
+    fn mock_dependencies_with_nfts(contract_balance: &[Coin]) -> OwnedDeps<MockStorage, MockApi, NftQuerier> {
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier: NftQuerier {
+                base: MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]),
+                nfts: HashMap::new(),
+                supplies: HashMap::new(),
+                uninitialized_supplies: HashSet::new(),
+                cw20_balances: HashMap::new(),
+                exchange_rates: HashMap::new(),
+                price_caps: HashMap::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn proper_instantiate() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let info = mock_info("creator", &[]);
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn receive_nft() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let receive_info = mock_info("contract1", &[]);
+        let owner_of_msg = OwnerOfResponse {
+            owner: "sender".to_string(),
+            approvals: vec![],
+        };
+
+        let receive_msg = Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: "token1".to_string(),
+            msg: Some(to_binary(&owner_of_msg).unwrap()),
+        };
+
+        // Unfortunately, Mock, who checks Wasm, is not yet implemented.
+        // So this test always fails.
+        // I think it will be supported later.
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), receive_info.clone(), receive_msg);
+        println!("{:?}", res);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn receive_nft_empty_msg() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let receive_info = mock_info("contract1", &[]);
+        let token_info_msg = TokenInfoMsg {
+            contract: "contract2".to_string(),
+            description: Some("No description".to_string()),
+            price: coin(1000000, "umed"),
+            sender: "sender".to_string(),
+        };
+
+        let receive_msg = Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: "token1".to_string(),
+            msg: Some(to_binary(&token_info_msg).unwrap()),
+        };
+
+        // Unfortunately, Mock, who checks Wasm, is not yet implemented.
+        // So this test always fails.
+        // I think it will be supported later.
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), receive_info.clone(), receive_msg);
+        println!("{:?}", res);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn recover_owner() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let contract = "contract1";
+        let sender = "sender";
+        let refund_token_id = "token1";
+
+        let msg = InstantiateMsg {
+            source_contracts: vec![contract.to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let receive_info = mock_info(contract, &[]);
+
+        let owner_of_msg = OwnerOfResponse {
+            owner: sender.to_string(),
+            approvals: vec![],
+        };
+
+        let receive_msg = Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: refund_token_id.to_string(),
+            msg: Some(to_binary(&owner_of_msg).unwrap()),
+        };
+
+        // Unfortunately, Mock, who checks Wasm, is not yet implemented.
+        // So this test always fails.
+        // I think it will be supported later.
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), receive_info.clone(), receive_msg);
+        assert!(res.is_err());
+
+        let res = execute_recover_owner(deps.as_mut(), env.clone(), info.clone(), contract.to_string(), refund_token_id.to_string());
+        let response = res.unwrap();
+        let cosmos_msg = response.messages[0].clone();
+
+        if let CosmosMsg::Wasm(wasm_msg) = cosmos_msg {
+            if let WasmMsg::Execute { contract_addr, msg, send } = wasm_msg {
+                assert_eq!(contract, contract_addr);
+
+                if let cw721_base::msg::ExecuteMsg::TransferNft { recipient, token_id } = from_binary(&msg).unwrap() {
+                    assert_eq!(sender, recipient);
+                    assert_eq!(token_id, refund_token_id);
+                }
+            }
+        }
+
+        assert_eq!("action", response.attributes[0].key);
+        assert_eq!("recover_owner", response.attributes[0].value);
+        assert_eq!("sender", response.attributes[1].key);
+        assert_eq!(sender.to_string(), response.attributes[1].value);
+        assert_eq!("sender_contract", response.attributes[2].key);
+        assert_eq!(contract.to_string(), response.attributes[2].value);
+        assert_eq!("token_id", response.attributes[3].key);
+        assert_eq!(refund_token_id.to_string(), response.attributes[3].value);
+    }
+
+    #[test]
+    fn refund() {
+        let mut deps = mock_dependencies(&[coin(1000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_wind_down(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+
+        let mut env = env;
+        env.block.time = env.block.time.plus_seconds(DEFAULT_WIND_DOWN_GRACE_SECONDS);
+        let res = execute_refund(deps.as_mut(), env, info);
+        println!("{:?}", res);
+        assert!(res.is_ok());
+
+        let response = res.unwrap();
+        let cosmos_msg = response.messages[0].clone();
+
+        if let CosmosMsg::Bank(BankMsg::Send {to_address, amount}) = cosmos_msg {
+            assert_eq!("creator", to_address);
+        }
+        assert_eq!("action", response.attributes[0].key);
+        assert_eq!("refund", response.attributes[0].value);
+        assert_eq!("sender_contract", response.attributes[1].key);
+        assert_eq!("creator", response.attributes[1].value);
+        assert_eq!("refund", response.attributes[2].key);
+    }
+
+    #[test]
+    fn deposit() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string(), "contract2".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let deposit_info = mock_info("creator", &[coin(1000, "umed")]);
+        let res = execute_deposit(deps.as_mut(), env.clone(), deposit_info.clone(), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), deposit_info.clone(), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), deposit_info.clone(), None);
+        assert!(res.is_ok());
+
+        let budget = query_budget(deps.as_ref(), "contract1".to_string()).unwrap();
+        assert_eq!(vec![coin(2000, "umed")], budget.amount);
+        let budget = query_budget(deps.as_ref(), "contract2".to_string()).unwrap();
+        assert!(budget.amount.is_empty());
+        let unallocated = query_unallocated(deps.as_ref()).unwrap();
+        assert_eq!(vec![coin(1000, "umed")], unallocated.amount);
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), deposit_info.clone(), Some("contract3".to_string()));
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn allocate() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let deposit_info = mock_info("creator", &[coin(1000, "umed")]);
+        let res = execute_deposit(deps.as_mut(), env.clone(), deposit_info, None);
+        assert!(res.is_ok());
+
+        let res = execute_allocate(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), coin(400, "umed"));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let res = execute_allocate(deps.as_mut(), env.clone(), info.clone(), "contract1".to_string(), coin(400, "umed"));
+        assert!(res.is_ok());
+        assert_eq!(vec![coin(400, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+        assert_eq!(vec![coin(600, "umed")], query_unallocated(deps.as_ref()).unwrap().amount);
+
+        let res = execute_allocate(deps.as_mut(), env.clone(), info.clone(), "contract1".to_string(), coin(601, "umed"));
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn receive_nft_debits_budget() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(3000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string(), "contract2".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(2000, "umed")]), Some("contract2".to_string()));
+        assert!(res.is_ok());
+
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(600, "umed"));
+        deps.querier.add_nft("contract1", "token2", MOCK_CONTRACT_ADDR, coin(600, "umed"));
+
+        let receive_msg = |token_id: &str| Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token1"));
+        let response = res.unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(600, "umed")] }), response.messages[0]);
+        assert_eq!(vec![coin(400, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+        assert_eq!(vec![coin(2000, "umed")], query_budget(deps.as_ref(), "contract2".to_string()).unwrap().amount);
+
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(1200, "umed")]);
+        deps.querier.supplies.insert("contract2".to_string(), vec![]);
+        let reserves = query_reserves(deps.as_ref()).unwrap();
+        assert_eq!(vec![coin(600, "umed")], reserves.liabilities);
+        assert_eq!(vec![coin(2000, "umed")], reserves.excess);
+
+        // The budget of contract2 must not be used for the tokens of contract1.
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2"));
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn refund_excess() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1100, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: Some(100),
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(100, "umed")]), None);
+        assert!(res.is_ok());
+
+        // The liability of contract1 is unknown, so its whole budget is reserved.
+        let reserves = query_reserves(deps.as_ref()).unwrap();
+        assert_eq!(vec![coin(1000, "umed")], reserves.liabilities);
+        assert_eq!(vec![coin(100, "umed")], reserves.excess);
+
+        // The supply does not count the tokens minted before it was tracked.
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(600, "umed")]);
+        deps.querier.uninitialized_supplies.insert("contract1".to_string());
+        let reserves = query_reserves(deps.as_ref()).unwrap();
+        assert_eq!(vec![coin(1000, "umed")], reserves.liabilities);
+
+        deps.querier.uninitialized_supplies.clear();
+        let reserves = query_reserves(deps.as_ref()).unwrap();
+        assert_eq!(vec![coin(600, "umed")], reserves.liabilities);
+        assert_eq!(vec![coin(500, "umed")], reserves.excess);
+        assert_eq!(None, reserves.refundable_at);
+
+        let res = execute_refund(deps.as_mut(), env.clone(), mock_info("sender", &[]));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let response = execute_refund(deps.as_mut(), env.clone(), info.clone()).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: vec![coin(500, "umed")] }), response.messages[0]);
+        assert_eq!(vec![coin(600, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+        assert!(query_unallocated(deps.as_ref()).unwrap().amount.is_empty());
+
+        let res = execute_refund(deps.as_mut(), env.clone(), info.clone());
+        match res.unwrap_err() {
+            ContractError::NothingToRefund {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn refund_after_wind_down() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let mut env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: Some(100),
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(1000, "umed")]);
+
+        let res = execute_wind_down(deps.as_mut(), env.clone(), mock_info("sender", &[]));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let res = execute_wind_down(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        assert_eq!(Some(env.block.time.plus_seconds(100)), query_reserves(deps.as_ref()).unwrap().refundable_at);
+
+        // The outstanding tokens can still be redeemed during the grace period.
+        env.block.time = env.block.time.plus_seconds(99);
+        let res = execute_refund(deps.as_mut(), env.clone(), info.clone());
+        match res.unwrap_err() {
+            ContractError::NothingToRefund {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.time = env.block.time.plus_seconds(1);
+        let response = execute_refund(deps.as_mut(), env.clone(), info.clone()).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: vec![coin(1000, "umed")] }), response.messages[0]);
+        assert!(query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount.is_empty());
+    }
+
+    #[test]
+    fn refund_after_wind_down_settles_claims() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(500, "umed")]);
+        let mut env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: Some(100),
+            iou_enabled: Some(true),
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(500, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(600, "umed"));
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(600, "umed")]);
+        let receive_msg = Cw721ReceiveMsg {
+            sender: "sender1".to_string(),
+            token_id: "token1".to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender1".to_string(), approvals: vec![] }).unwrap()),
+        };
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg);
+        assert!(res.is_ok());
+        deps.querier.base.update_balance(MOCK_CONTRACT_ADDR, vec![coin(150, "umed")]);
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(50, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(100, "umed")]), None);
+        assert!(res.is_ok());
+
+        let res = execute_cancel_wind_down(deps.as_mut(), env.clone(), info.clone());
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_wind_down(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        let res = execute_cancel_wind_down(deps.as_mut(), env.clone(), mock_info("sender1", &[]));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_cancel_wind_down(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        assert_eq!(None, query_reserves(deps.as_ref()).unwrap().refundable_at);
+
+        let res = execute_wind_down(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        env.block.time = env.block.time.plus_seconds(100);
+
+        // The claim is paid from its budget before the depositors.
+        let response = execute_refund(deps.as_mut(), env.clone(), info.clone()).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender1".to_string(), amount: vec![coin(50, "umed")] }), response.messages[0]);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: vec![coin(100, "umed")] }), response.messages[1]);
+        assert!(query_claims(deps.as_ref(), "sender1".to_string(), None, None).unwrap().claims.is_empty());
+        assert!(query_reserves(deps.as_ref()).unwrap().liabilities.is_empty());
+    }
+
+    #[test]
+    fn claim_outstanding_limits() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: Some(true),
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        for token_id in ["token1", "token2", "token3"].iter() {
+            deps.querier.add_nft("contract1", token_id, MOCK_CONTRACT_ADDR, coin(100, "umed"));
+            let receive_msg = Cw721ReceiveMsg {
+                sender: "sender1".to_string(),
+                token_id: token_id.to_string(),
+                msg: Some(to_binary(&OwnerOfResponse { owner: "sender1".to_string(), approvals: vec![] }).unwrap()),
+            };
+            let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg);
+            assert!(res.is_ok());
+        }
+
+        let claims = query_claims(deps.as_ref(), "sender1".to_string(), None, Some(2)).unwrap().claims;
+        assert_eq!(vec![1, 2], claims.iter().map(|claim| claim.id).collect::<Vec<u64>>());
+        let claims = query_claims(deps.as_ref(), "sender1".to_string(), Some(2), None).unwrap().claims;
+        assert_eq!(vec![3], claims.iter().map(|claim| claim.id).collect::<Vec<u64>>());
+        assert!(query_claims(deps.as_ref(), "sender2".to_string(), None, None).unwrap().claims.is_empty());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(300, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let rate_limits = RateLimits {
+            max_redemptions_per_day: Some(1),
+            max_payout_per_window: vec![coin(250, "umed")],
+            window_seconds: 60 * 60,
+            max_payout_per_block: vec![],
+        };
+        let res = execute_update_rate_limits(deps.as_mut(), env.clone(), info.clone(), Some(rate_limits));
+        assert!(res.is_ok());
+        let res = execute_update_circuit_breaker(deps.as_mut(), env.clone(), info.clone(), Some(CircuitBreaker { threshold: vec![coin(1000, "umed")], window_seconds: 60 * 60 }));
+        assert!(res.is_ok());
+
+        // The claims are not counted as redemptions, but their payouts are capped.
+        let response = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender1", &[])).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender1".to_string(), amount: vec![coin(200, "umed")] }), response.messages[0]);
+        assert_eq!(attr("rate_limited", "max payout 250umed per 3600 seconds"), response.attributes[4]);
+        // the rate limited claim is not counted by the circuit breaker
+        assert_eq!(vec![coin(200, "umed")], query_status(deps.as_ref(), env.clone()).unwrap().window_payout);
+        let claims = query_claims(deps.as_ref(), "sender1".to_string(), None, None).unwrap().claims;
+        assert_eq!(vec![3], claims.iter().map(|claim| claim.id).collect::<Vec<u64>>());
+
+        let res = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender1", &[]));
+        match res.unwrap_err() {
+            ContractError::RateLimitExceeded { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn receive_nft_iou() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(10000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: Some(true),
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(500, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(600, "umed"));
+        deps.querier.add_nft("contract1", "token2", MOCK_CONTRACT_ADDR, coin(300, "umed"));
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(900, "umed")]);
+
+        let receive_msg = |sender: &str, token_id: &str| Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: sender.to_string(), approvals: vec![] }).unwrap()),
+        };
+
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender1", "token1")).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender1".to_string(), amount: vec![coin(500, "umed")] }), response.messages[0]);
+        assert_eq!(attr("outstanding", "100umed"), response.attributes[6]);
+
+        // Nothing is paid now, but the token is still taken over.
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender2", "token2")).unwrap();
+        assert_eq!(1, response.messages.len());
+        assert_eq!(attr("outstanding", "300umed"), response.attributes[6]);
+
+        let claims = query_claims(deps.as_ref(), "sender2".to_string(), None, None).unwrap().claims;
+        assert_eq!(1, claims.len());
+        assert_eq!(2, claims[0].id);
+        assert_eq!(coin(300, "umed"), claims[0].outstanding);
+        assert_eq!(vec![coin(400, "umed")], query_reserves(deps.as_ref()).unwrap().liabilities);
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(200, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        // The claim of sender1 is earlier, so sender2 gets only what is left after it.
+        let response = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender2", &[])).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender2".to_string(), amount: vec![coin(100, "umed")] }), response.messages[0]);
+        assert_eq!(coin(200, "umed"), query_claims(deps.as_ref(), "sender2".to_string(), None, None).unwrap().claims[0].outstanding);
+
+        let response = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender1", &[])).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender1".to_string(), amount: vec![coin(100, "umed")] }), response.messages[0]);
+        assert!(query_claims(deps.as_ref(), "sender1".to_string(), None, None).unwrap().claims.is_empty());
+
+        let res = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender2", &[]));
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender1", &[]));
+        match res.unwrap_err() {
+            ContractError::NoOutstandingClaims {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(vec![coin(200, "umed")], query_reserves(deps.as_ref()).unwrap().liabilities);
+    }
+
+    #[test]
+    fn parse_legacy_token_info() {
+        let token_info = parse_token_info(r#"{"price":{"denom":"umed","amount":"1000"}}"#).unwrap();
+        assert_eq!(Asset::Native(coin(1000, "umed")), token_info.price);
+
+        let token_info = parse_token_info(r#"{"price":{"cw20":{"address":"cw20_token","amount":"1000"}}}"#).unwrap();
+        assert_eq!(Asset::Cw20(Cw20Coin { address: "cw20_token".to_string(), amount: Uint128(1000) }), token_info.price);
+    }
+
+    #[test]
+    fn receive_nft_cw20() {
+        let mut deps = mock_dependencies_with_nfts(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let cw20_receive_msg = Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128(1000),
+            msg: to_binary(&ReceiveMsg::Deposit { source_contract: Some("contract1".to_string()) }).unwrap(),
+        };
+        let res = execute_receive(deps.as_mut(), env.clone(), mock_info("cw20_token", &[]), cw20_receive_msg.clone());
+        match res.unwrap_err() {
+            ContractError::UnacceptedDenom { denom } => assert_eq!("cw20_token", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let res = execute_register_cw20_tokens(deps.as_mut(), env.clone(), mock_info("sender", &[]), vec!["cw20_token".to_string()]);
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_register_cw20_tokens(deps.as_mut(), env.clone(), info.clone(), vec!["cw20_token".to_string()]);
+        assert!(res.is_ok());
+
+        let res = execute_receive(deps.as_mut(), env.clone(), mock_info("cw20_token", &[]), cw20_receive_msg);
+        assert!(res.is_ok());
+        assert_eq!(vec![coin(1000, "cw20_token")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+
+        deps.querier.cw20_balances.insert("cw20_token".to_string(), Uint128(1000));
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, Asset::Cw20(Cw20Coin { address: "cw20_token".to_string(), amount: Uint128(600) }));
+
+        let receive_msg = Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: "token1".to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg).unwrap();
+        assert_eq!(2, response.messages.len());
+        assert_eq!(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "cw20_token".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: "sender".to_string(), amount: Uint128(600) }).unwrap(),
+            send: vec![],
+        }), response.messages[0]);
+        assert_eq!(vec![coin(400, "cw20_token")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+
+        // The CW20 balance is checked as well as the budget.
+        deps.querier.cw20_balances.insert("cw20_token".to_string(), Uint128(100));
+        deps.querier.add_nft("contract1", "token2", MOCK_CONTRACT_ADDR, Asset::Cw20(Cw20Coin { address: "cw20_token".to_string(), amount: Uint128(400) }));
+        let receive_msg = Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: "token2".to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg);
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn receive_nft_conversion() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        let conversion = Conversion {
+            from_denom: "uusd".to_string(),
+            to_denom: "umed".to_string(),
+            rate: Some(Decimal::percent(250)),
+            min_rate: None,
+            max_rate: None,
+        };
+        let res = execute_set_conversion(deps.as_mut(), env.clone(), mock_info("sender", &[]), conversion.clone());
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_conversions = vec![
+            Conversion { to_denom: "uusd".to_string(), ..conversion.clone() },
+            Conversion { rate: Some(Decimal::zero()), ..conversion.clone() },
+            Conversion { min_rate: Some(Decimal::percent(300)), max_rate: Some(Decimal::percent(200)), ..conversion.clone() },
+        ];
+        for invalid_conversion in invalid_conversions {
+            match execute_set_conversion(deps.as_mut(), env.clone(), info.clone(), invalid_conversion).unwrap_err() {
+                ContractError::InvalidParameter { .. } => {}
+                e => panic!("unexpected error: {:?}", e),
+            }
+        }
+        let res = execute_set_conversion(deps.as_mut(), env.clone(), info.clone(), conversion);
+        assert!(res.is_ok());
+        let res = execute_remove_conversion(deps.as_mut(), env.clone(), mock_info("sender", &[]), "uusd".to_string());
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(100, "uusd"));
+        deps.querier.add_nft("contract1", "token2", MOCK_CONTRACT_ADDR, coin(100, "uusd"));
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(200, "uusd")]);
+        assert_eq!(vec![coin(500, "umed")], query_reserves(deps.as_ref()).unwrap().liabilities);
+
+        let receive_msg = |token_id: &str| Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token1")).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(250, "umed")] }), response.messages[0]);
+        assert_eq!(attr("price", "100uusd"), response.attributes[4]);
+        assert_eq!(attr("rate", "2.5"), response.attributes[5]);
+        assert_eq!(attr("payout", "250umed"), response.attributes[6]);
+        assert_eq!(vec![coin(750, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+        assert_eq!(vec![coin(250, "umed")], query_reserves(deps.as_ref()).unwrap().liabilities);
+
+        // use the rate of the oracle within the bounds
+        let conversion = Conversion {
+            from_denom: "uusd".to_string(),
+            to_denom: "umed".to_string(),
+            rate: None,
+            min_rate: Some(Decimal::percent(100)),
+            max_rate: Some(Decimal::percent(300)),
+        };
+        let res = execute_set_conversion(deps.as_mut(), env.clone(), info.clone(), conversion);
+        assert!(res.is_ok());
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2"));
+        match res.unwrap_err() {
+            ContractError::NoExchangeRate { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let res = execute_set_oracle(deps.as_mut(), env.clone(), mock_info("sender", &[]), Some("oracle".to_string()));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_oracle(deps.as_mut(), env.clone(), info.clone(), Some("oracle".to_string()));
+        assert!(res.is_ok());
+        deps.querier.exchange_rates.insert(("uusd".to_string(), "umed".to_string()), Decimal::percent(400));
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2"));
+        match res.unwrap_err() {
+            ContractError::ExchangeRateOutOfBounds { rate } => assert_eq!(Decimal::percent(400), rate),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        // reserved at the highest allowed rate while the rate is out of bounds
+        assert_eq!(vec![coin(300, "umed")], query_reserves(deps.as_ref()).unwrap().liabilities);
+
+        deps.querier.exchange_rates.insert(("uusd".to_string(), "umed".to_string()), Decimal::percent(200));
+        assert_eq!(Decimal::percent(200), query_conversion(deps.as_ref(), "uusd".to_string()).unwrap().rate);
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2")).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(200, "umed")] }), response.messages[0]);
+        assert_eq!(attr("rate", "2"), response.attributes[5]);
+
+        // without a conversion, the price in another denom cannot be paid
+        let res = execute_remove_conversion(deps.as_mut(), env.clone(), info.clone(), "uusd".to_string());
+        assert!(res.is_ok());
+        deps.querier.add_nft("contract1", "token3", MOCK_CONTRACT_ADDR, coin(100, "uusd"));
+        match execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token3")).unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn receive_nft_fee() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: Some(true),
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(150, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        let fee_config = FeeConfig {
+            treasury: "treasury".to_string(),
+            percent: Decimal::percent(10),
+            flat: vec![coin(5, "umed")],
+        };
+        let res = execute_update_fee_config(deps.as_mut(), env.clone(), mock_info("sender", &[]), Some(fee_config.clone()));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_fee_config(deps.as_mut(), env.clone(), info.clone(), Some(FeeConfig { percent: Decimal::percent(101), ..fee_config.clone() }));
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_fee_config(deps.as_mut(), env.clone(), info.clone(), Some(FeeConfig { treasury: "x".to_string(), ..fee_config.clone() }));
+        match res.unwrap_err() {
+            ContractError::Std(_) => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_fee_config(deps.as_mut(), env.clone(), info.clone(), Some(fee_config.clone()));
+        assert!(res.is_ok());
+        assert_eq!(Some(fee_config.clone()), query_fee_config(deps.as_ref()).unwrap().fee_config);
+
+        // the fee never exceeds the payout
+        assert_eq!(coin(3, "umed"), calculate_fee(&fee_config, &coin(3, "umed"), true));
+        assert_eq!(coin(0, "umed"), calculate_fee(&fee_config, &coin(3, "umed"), false));
+
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(100, "umed"));
+        deps.querier.add_nft("contract1", "token2", MOCK_CONTRACT_ADDR, coin(100, "umed"));
+
+        let receive_msg = |token_id: &str| Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token1")).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(85, "umed")] }), response.messages[0]);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "treasury".to_string(), amount: vec![coin(15, "umed")] }), response.messages[1]);
+        assert_eq!(attr("fee", "15umed"), response.attributes[5]);
+
+        // only 50umed is paid now and the flat fee is not taken again for the claim
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2")).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(40, "umed")] }), response.messages[0]);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "treasury".to_string(), amount: vec![coin(10, "umed")] }), response.messages[1]);
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(50, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let response = execute_claim_outstanding(deps.as_mut(), env.clone(), mock_info("sender", &[])).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(45, "umed")] }), response.messages[0]);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "treasury".to_string(), amount: vec![coin(5, "umed")] }), response.messages[1]);
+
+        let res = execute_update_fee_config(deps.as_mut(), env.clone(), mock_info("sender", &[]), None);
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_fee_config(deps.as_mut(), env.clone(), info.clone(), None);
+        assert!(res.is_ok());
+        assert_eq!(None, query_fee_config(deps.as_ref()).unwrap().fee_config);
+    }
+
+    #[test]
+    fn receive_nft_rate_limits() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let mut env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        let rate_limits = RateLimits {
+            max_redemptions_per_day: Some(2),
+            max_payout_per_window: vec![coin(250, "umed")],
+            window_seconds: 60 * 60,
+            max_payout_per_block: vec![coin(150, "umed")],
+        };
+        let res = execute_update_rate_limits(deps.as_mut(), env.clone(), mock_info("sender", &[]), Some(rate_limits.clone()));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_rate_limits(deps.as_mut(), env.clone(), info.clone(), Some(rate_limits.clone()));
+        assert!(res.is_ok());
+
+        for token_id in ["token1", "token2", "token3", "token4"].iter() {
+            deps.querier.add_nft("contract1", token_id, MOCK_CONTRACT_ADDR, coin(100, "umed"));
+        }
+        let receive_msg = |sender: &str, token_id: &str| Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: sender.to_string(), approvals: vec![] }).unwrap()),
+        };
+
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender", "token1"));
+        assert!(res.is_ok());
+        let limits = query_limits(deps.as_ref(), env.clone(), "sender".to_string()).unwrap();
+        assert_eq!(Some(rate_limits), limits.rate_limits);
+        assert_eq!(Some(1), limits.remaining_redemptions);
+        assert_eq!(vec![coin(150, "umed")], limits.remaining_payout);
+        assert_eq!(vec![coin(50, "umed")], limits.remaining_block_payout);
+
+        // the payouts of the other senders count to the block ceiling
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender2", "token2"));
+        match res.unwrap_err() {
+            ContractError::RateLimitExceeded { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_seconds(60);
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender", "token2"));
+        assert!(res.is_ok());
+
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_seconds(60 * 60);
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender", "token3"));
+        match res.unwrap_err() {
+            ContractError::RateLimitExceeded { msg } => assert_eq!("max 2 redemptions per day", msg),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let limits = query_limits(deps.as_ref(), env.clone(), "sender".to_string()).unwrap();
+        assert_eq!(Some(0), limits.remaining_redemptions);
+        assert_eq!(vec![coin(250, "umed")], limits.remaining_payout);
+        assert_eq!(vec![coin(150, "umed")], limits.remaining_block_payout);
+
+        env.block.time = env.block.time.plus_seconds(SECONDS_PER_DAY);
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("sender", "token3"));
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn receive_nft_redeem_window() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let mut env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string(), "contract2".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: Some(env.block.time.plus_seconds(60)),
+            redeem_end: Some(env.block.time.plus_seconds(120)),
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let invalid_msg = InstantiateMsg { redeem_end: Some(env.block.time), ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(500, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(500, "umed")]), Some("contract2".to_string()));
+        assert!(res.is_ok());
+
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(100, "umed"));
+        deps.querier.add_nft("contract2", "token1", MOCK_CONTRACT_ADDR, coin(100, "umed"));
+        let receive_msg = Cw721ReceiveMsg {
+            sender: "sender".to_string(),
             token_id: "token1".to_string(),
-            msg: Some(to_binary(&owner_of_msg).unwrap()),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
         };
 
-        // Unfortunately, Mock, who checks Wasm, is not yet implemented.
-        // So this test always fails.
-        // I think it will be supported later.
-        let res = execute_receive_nft(deps.as_mut(), env.clone(), receive_info.clone(), receive_msg);
-        println!("{:?}", res);
-        assert!(res.is_err());
+        // bounced back before the window
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg.clone()).unwrap();
+        let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft { recipient: "sender".to_string(), token_id: "token1".to_string() };
+        assert_eq!(vec![CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "contract1".to_string(), msg: to_binary(&transfer_msg).unwrap(), send: vec![] })], response.messages);
+        assert_eq!(attr("action", "bounce_nft"), response.attributes[0]);
+        assert_eq!(attr("reason", ContractError::OutsideRedeemWindow { time: env.block.time }.to_string()), response.attributes[4]);
+
+        let redeem_window = RedeemWindow { redeem_start: None, redeem_end: Some(env.block.time.plus_seconds(60)) };
+        let res = execute_set_redeem_window(deps.as_mut(), env.clone(), mock_info("sender", &[]), Some("contract2".to_string()), redeem_window.clone());
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), Some("contract3".to_string()), redeem_window.clone());
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), Some("contract2".to_string()), redeem_window.clone());
+        assert!(res.is_ok());
+        assert_eq!(redeem_window, query_redeem_window(deps.as_ref(), "contract2".to_string()).unwrap().redeem_window);
+
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract2", &[]), receive_msg.clone()).unwrap();
+        assert_eq!(attr("action", "receive_nft"), response.attributes[0]);
+
+        env.block.time = env.block.time.plus_seconds(60);
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg.clone()).unwrap();
+        assert_eq!(attr("action", "receive_nft"), response.attributes[0]);
+
+        // the window of the contract does not apply to contract2
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract2", &[]), receive_msg.clone()).unwrap();
+        assert_eq!(attr("action", "bounce_nft"), response.attributes[0]);
+
+        // bounced back at the end of the window
+        env.block.time = env.block.time.plus_seconds(60);
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg).unwrap();
+        assert_eq!(attr("action", "bounce_nft"), response.attributes[0]);
+
+        let res = execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), None, RedeemWindow { redeem_start: Some(env.block.time), redeem_end: Some(env.block.time) });
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
     }
 
     #[test]
-    fn receive_nft_empty_msg() {
-        let mut deps = mock_dependencies(&[]);
-        let env = mock_env();
+    fn receive_nft_paused() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let mut env = mock_env();
         let info = mock_info("creator", &[]);
         let msg = InstantiateMsg {
             source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
         };
 
         let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
         assert!(res.is_ok());
 
-        let receive_info = mock_info("contract1", &[]);
-        let token_info_msg = TokenInfoMsg {
-            contract: "contract2".to_string(),
-            description: Some("No description".to_string()),
-            price: coin(1000000, "umed"),
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        for token_id in ["token1", "token2", "token3"].iter() {
+            deps.querier.add_nft("contract1", token_id, MOCK_CONTRACT_ADDR, coin(100, "umed"));
+        }
+        let receive_msg = |token_id: &str| Cw721ReceiveMsg {
             sender: "sender".to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+
+        let res = execute_pause(deps.as_mut(), env.clone(), mock_info("sender", &[]));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_pause(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        assert_eq!("paused by the payer", query_status(deps.as_ref(), env.clone()).unwrap().paused.unwrap().reason);
+
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token1")).unwrap();
+        let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft { recipient: "sender".to_string(), token_id: "token1".to_string() };
+        assert_eq!(vec![CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "contract1".to_string(), msg: to_binary(&transfer_msg).unwrap(), send: vec![] })], response.messages);
+        assert_eq!(attr("action", "bounce_nft"), response.attributes[0]);
+        assert_eq!(vec![coin(1000, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+
+        let res = execute_unpause(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        assert_eq!(None, query_status(deps.as_ref(), env.clone()).unwrap().paused);
+
+        let circuit_breaker = CircuitBreaker { threshold: vec![coin(150, "umed")], window_seconds: 60 * 60 };
+        let res = execute_update_circuit_breaker(deps.as_mut(), env.clone(), info.clone(), Some(circuit_breaker.clone()));
+        assert!(res.is_ok());
+
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token1")).unwrap();
+        assert_eq!(attr("action", "receive_nft"), response.attributes[0]);
+        let status = query_status(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(Some(circuit_breaker), status.circuit_breaker);
+        assert_eq!(vec![coin(100, "umed")], status.window_payout);
+
+        // trips the circuit breaker and stays paused
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2")).unwrap();
+        assert_eq!(attr("action", "bounce_nft"), response.attributes[0]);
+        assert_eq!(vec![coin(900, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+        assert!(query_status(deps.as_ref(), env.clone()).unwrap().paused.is_some());
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token3")).unwrap();
+        assert_eq!(attr("action", "bounce_nft"), response.attributes[0]);
+
+        let res = execute_unpause(deps.as_mut(), env.clone(), mock_info("sender", &[]));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_unpause(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2")).unwrap();
+        assert_eq!(attr("action", "receive_nft"), response.attributes[0]);
+        assert_eq!(vec![coin(100, "umed")], query_status(deps.as_ref(), env.clone()).unwrap().window_payout);
+
+        // the payouts leave the window after window_seconds
+        env.block.time = env.block.time.plus_seconds(60 * 60);
+        assert!(query_status(deps.as_ref(), env.clone()).unwrap().window_payout.is_empty());
+
+        let res = execute_update_circuit_breaker(deps.as_mut(), env.clone(), mock_info("sender", &[]), None);
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_circuit_breaker(deps.as_mut(), env.clone(), info.clone(), Some(CircuitBreaker { threshold: vec![coin(150, "umed")], window_seconds: 0 }));
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn receive_nft_receipt() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: Some("contract1".to_string()),
+            accepted_denoms: None,
         };
 
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), InstantiateMsg { receipt_contract: None, ..msg });
+        assert!(res.is_ok());
+
+        let res = execute_set_receipt_contract(deps.as_mut(), env.clone(), mock_info("sender", &[]), Some("receipts".to_string()));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_receipt_contract(deps.as_mut(), env.clone(), info.clone(), Some("receipts".to_string()));
+        assert!(res.is_ok());
+        assert_eq!(Some("receipts".to_string()), query_contract_info(deps.as_ref()).unwrap().receipt_contract);
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(100, "umed"));
         let receive_msg = Cw721ReceiveMsg {
             sender: "sender".to_string(),
             token_id: "token1".to_string(),
-            msg: Some(to_binary(&token_info_msg).unwrap()),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
         };
+        let response = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg).unwrap();
 
-        // Unfortunately, Mock, who checks Wasm, is not yet implemented.
-        // So this test always fails.
-        // I think it will be supported later.
-        let res = execute_receive_nft(deps.as_mut(), env.clone(), receive_info.clone(), receive_msg);
-        println!("{:?}", res);
-        assert!(res.is_err());
+        let mint_msg = ReceiptExecuteMsg::Mint(ReceiptMintMsg {
+            owner: "sender".to_string(),
+            name: "receipt of contract1 token1".to_string(),
+            description: None,
+            image: None,
+            price: coin(0, "umed").into(),
+            receipt: Some(Receipt {
+                source_contract: "contract1".to_string(),
+                token_id: "token1".to_string(),
+                paid: coin(100, "umed"),
+                redeemed_at: env.block.time,
+            }),
+        });
+        assert_eq!(CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "receipts".to_string(), msg: to_binary(&mint_msg).unwrap(), send: vec![] }), response.messages[2]);
     }
 
     #[test]
-    fn recover_owner() {
-        let mut deps = mock_dependencies(&[]);
-        let env = mock_env();
+    fn withdraw_pro_rata() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let mut env = mock_env();
         let info = mock_info("creator", &[]);
-        let contract = "contract1";
-        let sender = "sender";
-        let refund_token_id = "token1";
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: Some(100),
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
 
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(100, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("sponsor", &[coin(100, "umed")]), None);
+        assert!(res.is_ok());
+        deps.querier.supplies.insert("contract1".to_string(), vec![coin(100, "umed")]);
+
+        let depositors = query_depositors(deps.as_ref()).unwrap().depositors;
+        assert_eq!(vec!["creator", "sponsor"], depositors.iter().map(|d| d.depositor.as_str()).collect::<Vec<&str>>());
+        assert_eq!(ContributionInfo {
+            denom: "umed".to_string(),
+            deposited: Uint128(100),
+            shares: Uint128(100),
+            value: Uint128(100),
+            withdrawable: Uint128(50),
+        }, depositors[1].contributions[0]);
+
+        let res = execute_withdraw(deps.as_mut(), env.clone(), mock_info("sender", &[]));
+        match res.unwrap_err() {
+            ContractError::NothingToRefund {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let response = execute_withdraw(deps.as_mut(), env.clone(), mock_info("sponsor", &[])).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sponsor".to_string(), amount: vec![coin(50, "umed")] }), response.messages[0]);
+        assert_eq!(vec![coin(50, "umed")], query_unallocated(deps.as_ref()).unwrap().amount);
+
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("sponsor", &[]), None);
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_refund(deps.as_mut(), env.clone(), mock_info("sponsor", &[]));
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the reserved funds are shared pro-rata to the remaining shares
+        let response = execute_refund(deps.as_mut(), env.clone(), info.clone()).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: vec![coin(33, "umed")] }), response.messages[0]);
+        assert_eq!(vec![coin(17, "umed")], query_unallocated(deps.as_ref()).unwrap().amount);
+        assert_eq!(vec![coin(100, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+
+        let res = execute_wind_down(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+        env.block.time = env.block.time.plus_seconds(100);
+
+        // the funds sent directly to the contract go to the payer
+        let response = execute_refund(deps.as_mut(), env.clone(), info.clone()).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: vec![coin(950, "umed")] }), response.messages[0]);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sponsor".to_string(), amount: vec![coin(50, "umed")] }), response.messages[1]);
+        assert!(query_depositors(deps.as_ref()).unwrap().depositors.iter().all(|d| d.contributions.iter().all(|c| c.shares.is_zero())));
+        let res = execute_withdraw(deps.as_mut(), env.clone(), mock_info("sponsor", &[]));
+        match res.unwrap_err() {
+            ContractError::NothingToRefund {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn deposit_accepted_denoms_and_sweep() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed"), coin(50, "uatom")]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
         let msg = InstantiateMsg {
-            source_contracts: vec![contract.to_string()],
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: Some(vec!["umed".to_string()]),
         };
 
         let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
         assert!(res.is_ok());
 
-        let receive_info = mock_info(contract, &[]);
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(100, "umed"), coin(50, "uatom")]), None);
+        match res.unwrap_err() {
+            ContractError::UnacceptedDenom { denom } => assert_eq!("uatom", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(100, "umed")]), None);
+        assert!(res.is_ok());
 
-        let owner_of_msg = OwnerOfResponse {
-            owner: sender.to_string(),
-            approvals: vec![],
+        let res = execute_set_accepted_denoms(deps.as_mut(), env.clone(), mock_info("sender", &[]), None);
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_accepted_denoms(deps.as_mut(), env.clone(), info.clone(), Some(vec!["umed".to_string(), "cw20_token".to_string()]));
+        assert!(res.is_ok());
+        assert_eq!(Some(vec!["umed".to_string(), "cw20_token".to_string()]), query_contract_info(deps.as_ref()).unwrap().accepted_denoms);
+
+        // a CW20 token not in the accepted denoms cannot be deposited
+        let cw20_receive_msg = Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128(10),
+            msg: to_binary(&ReceiveMsg::Deposit { source_contract: None }).unwrap(),
         };
+        let res = execute_receive(deps.as_mut(), env.clone(), mock_info("cw20_other", &[]), cw20_receive_msg);
+        match res.unwrap_err() {
+            ContractError::UnacceptedDenom { denom } => assert_eq!("cw20_other", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
 
-        let receive_msg = Cw721ReceiveMsg {
-            sender: sender.to_string(),
-            token_id: refund_token_id.to_string(),
-            msg: Some(to_binary(&owner_of_msg).unwrap()),
+        let res = execute_sweep(deps.as_mut(), env.clone(), mock_info("sender", &[]), None);
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the CW20 tokens transferred directly to this contract are swept if listed
+        deps.querier.cw20_balances.insert("cw20_token".to_string(), Uint128(30));
+        let response = execute_sweep(deps.as_mut(), env.clone(), info.clone(), Some(vec!["cw20_token".to_string()])).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: vec![coin(900, "umed"), coin(50, "uatom")] }), response.messages[0]);
+        // a failing CW20 transfer does not revert the sweep of the other funds
+        assert_eq!(SubMsg {
+            id: CW20_REFUND_REPLY_ID,
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "cw20_token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: "creator".to_string(), amount: Uint128(30) }).unwrap(),
+                send: vec![],
+            }),
+            gas_limit: None,
+            reply_on: ReplyOn::Error,
+        }, response.submessages[0]);
+        assert_eq!(vec![coin(100, "umed")], query_unallocated(deps.as_ref()).unwrap().amount);
+
+        // nothing is left to sweep once the balances match the deposits
+        deps.querier.base.update_balance(MOCK_CONTRACT_ADDR, vec![coin(100, "umed")]);
+        deps.querier.cw20_balances.insert("cw20_token".to_string(), Uint128::zero());
+        let res = execute_sweep(deps.as_mut(), env.clone(), info.clone(), None);
+        match res.unwrap_err() {
+            ContractError::NothingToRefund {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // a registered CW20 token can be deposited even if it is not in the accepted denoms
+        let res = execute_register_cw20_tokens(deps.as_mut(), env.clone(), info.clone(), vec!["cw20_other".to_string()]);
+        assert!(res.is_ok());
+        let cw20_receive_msg = Cw20ReceiveMsg {
+            sender: "creator".to_string(),
+            amount: Uint128(10),
+            msg: to_binary(&ReceiveMsg::Deposit { source_contract: None }).unwrap(),
         };
+        let response = execute_receive(deps.as_mut(), env.clone(), mock_info("cw20_other", &[]), cw20_receive_msg).unwrap();
+        assert_eq!(attr("amount", "10cw20_other"), response.attributes[3]);
+    }
 
-        // Unfortunately, Mock, who checks Wasm, is not yet implemented.
-        // So this test always fails.
-        // I think it will be supported later.
-        let res = execute_receive_nft(deps.as_mut(), env.clone(), receive_info.clone(), receive_msg);
+    #[test]
+    fn redeem_voucher() {
+        use k256::ecdsa::{Signature, SigningKey};
+        use k256::ecdsa::signature::Signer;
+
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
+        let mut env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(150, "umed")]), None);
+        assert!(res.is_ok());
+
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let public_key = Binary::from(signing_key.verify_key().to_bytes().to_vec());
+        let res = execute_register_issuer(deps.as_mut(), env.clone(), mock_info("sender", &[]), "issuer1".to_string(), public_key.clone());
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_register_issuer(deps.as_mut(), env.clone(), info.clone(), "x".to_string(), public_key.clone());
         assert!(res.is_err());
+        let res = execute_register_issuer(deps.as_mut(), env.clone(), info.clone(), "issuer1".to_string(), public_key.clone());
+        assert!(res.is_ok());
+        assert_eq!(vec![IssuerInfo { issuer: "issuer1".to_string(), public_key }], query_issuers(deps.as_ref()).unwrap().issuers);
 
-        let res = execute_recover_owner(deps.as_mut(), env.clone(), info.clone(), contract.to_string(), refund_token_id.to_string());
-        let response = res.unwrap();
-        let cosmos_msg = response.messages[0].clone();
+        let voucher = Voucher {
+            contract: MOCK_CONTRACT_ADDR.to_string(),
+            issuer: "issuer1".to_string(),
+            recipient: "patient".to_string(),
+            amount: coin(100, "umed"),
+            nonce: 1,
+            expiry: env.block.time.plus_seconds(60),
+        };
+        let sign = |voucher: &Voucher| -> Binary {
+            let signature: Signature = signing_key.sign(&to_vec(voucher).unwrap());
+            Binary::from(signature.as_ref())
+        };
 
-        if let CosmosMsg::Wasm(wasm_msg) = cosmos_msg {
-            if let WasmMsg::Execute { contract_addr, msg, send } = wasm_msg {
-                assert_eq!(contract, contract_addr);
+        let forged = Voucher { amount: coin(150, "umed"), ..voucher.clone() };
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), forged, sign(&voucher));
+        match res.unwrap_err() {
+            ContractError::InvalidSignature {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
 
-                if let cw721_base::msg::ExecuteMsg::TransferNft { recipient, token_id } = from_binary(&msg).unwrap() {
-                    assert_eq!(sender, recipient);
-                    assert_eq!(token_id, refund_token_id);
-                }
-            }
+        let response = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), voucher.clone(), sign(&voucher)).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "patient".to_string(), amount: vec![coin(100, "umed")] }), response.messages[0]);
+        assert_eq!(vec![coin(50, "umed")], query_unallocated(deps.as_ref()).unwrap().amount);
+
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), voucher.clone(), sign(&voucher));
+        match res.unwrap_err() {
+            ContractError::NonceUsed { nonce } => assert_eq!(1, nonce),
+            e => panic!("unexpected error: {:?}", e),
         }
 
-        assert_eq!("action", response.attributes[0].key);
-        assert_eq!("recover_owner", response.attributes[0].value);
-        assert_eq!("sender", response.attributes[1].key);
-        assert_eq!(sender.to_string(), response.attributes[1].value);
-        assert_eq!("sender_contract", response.attributes[2].key);
-        assert_eq!(contract.to_string(), response.attributes[2].value);
-        assert_eq!("token_id", response.attributes[3].key);
-        assert_eq!(refund_token_id.to_string(), response.attributes[3].value);
+        let voucher = Voucher { nonce: 2, ..voucher };
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), voucher.clone(), sign(&voucher));
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let invalid = Voucher { recipient: "x".to_string(), nonce: 3, amount: coin(10, "umed"), ..voucher.clone() };
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), invalid.clone(), sign(&invalid));
+        assert!(res.is_err());
+
+        let rate_limits = RateLimits { max_redemptions_per_day: Some(1), max_payout_per_window: vec![], window_seconds: 0, max_payout_per_block: vec![] };
+        let res = execute_update_rate_limits(deps.as_mut(), env.clone(), info.clone(), Some(rate_limits));
+        assert!(res.is_ok());
+        let limited = Voucher { nonce: 3, amount: coin(10, "umed"), ..voucher.clone() };
+
+        // a tripped circuit breaker uses neither the nonce nor the rate limits
+        let res = execute_update_circuit_breaker(deps.as_mut(), env.clone(), info.clone(), Some(CircuitBreaker { threshold: vec![coin(5, "umed")], window_seconds: 60 * 60 }));
+        assert!(res.is_ok());
+        let response = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), limited.clone(), sign(&limited)).unwrap();
+        assert!(response.messages.is_empty());
+        assert_eq!("paused", response.attributes[1].key);
+        assert_eq!(Some(1), query_limits(deps.as_ref(), env.clone(), "patient".to_string()).unwrap().remaining_redemptions);
+        let res = execute_update_circuit_breaker(deps.as_mut(), env.clone(), info.clone(), None);
+        assert!(res.is_ok());
+        let res = execute_unpause(deps.as_mut(), env.clone(), info.clone());
+        assert!(res.is_ok());
+
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), limited.clone(), sign(&limited));
+        assert!(res.is_ok());
+        let limited = Voucher { nonce: 4, ..limited };
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), limited.clone(), sign(&limited));
+        match res.unwrap_err() {
+            ContractError::RateLimitExceeded { msg } => assert_eq!("max 1 redemptions per day", msg),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_update_rate_limits(deps.as_mut(), env.clone(), info.clone(), None);
+        assert!(res.is_ok());
+
+        let res = execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), None, RedeemWindow { redeem_start: None, redeem_end: Some(env.block.time) });
+        assert!(res.is_ok());
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), limited.clone(), sign(&limited));
+        match res.unwrap_err() {
+            ContractError::OutsideRedeemWindow { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), None, RedeemWindow { redeem_start: None, redeem_end: None });
+        assert!(res.is_ok());
+
+        let res = execute_remove_issuer(deps.as_mut(), env.clone(), mock_info("sender", &[]), "issuer1".to_string());
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_remove_issuer(deps.as_mut(), env.clone(), info.clone(), "issuer1".to_string());
+        assert!(res.is_ok());
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), limited.clone(), sign(&limited));
+        match res.unwrap_err() {
+            ContractError::Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.time = env.block.time.plus_seconds(60);
+        let res = execute_redeem_voucher(deps.as_mut(), env.clone(), mock_info("relayer", &[]), voucher.clone(), sign(&voucher));
+        match res.unwrap_err() {
+            ContractError::VoucherExpired {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
     }
 
     #[test]
-    fn refund() {
-        let mut deps = mock_dependencies(&[]);
+    fn receive_nft_price_ceiling() {
+        let mut deps = mock_dependencies_with_nfts(&[coin(1000, "umed")]);
         let env = mock_env();
         let info = mock_info("creator", &[]);
         let msg = InstantiateMsg {
             source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
         };
 
         let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
         assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(1000, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
 
-        let res = execute_refund(deps.as_mut(), env, info);
-        println!("{:?}", res);
+        let res = execute_set_price_ceilings(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), vec![coin(100, "umed")]);
+        match res.unwrap_err() {
+            ContractError::UnmatchedPayer {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_price_ceilings(deps.as_mut(), env.clone(), info.clone(), "contract2".to_string(), vec![coin(100, "umed")]);
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_set_price_ceilings(deps.as_mut(), env.clone(), info.clone(), "contract1".to_string(), vec![coin(100, "umed")]);
         assert!(res.is_ok());
 
-        let response = res.unwrap();
-        let cosmos_msg = response.messages[0].clone();
+        let price_ceilings = query_price_ceilings(deps.as_ref(), "contract1".to_string()).unwrap();
+        assert_eq!(vec![coin(100, "umed")], price_ceilings.ceilings);
+        assert_eq!(None, price_ceilings.price_caps);
 
-        if let CosmosMsg::Bank(BankMsg::Send {to_address, amount}) = cosmos_msg {
-            assert_eq!("creator", to_address);
+        let receive_msg = |token_id: &str| Cw721ReceiveMsg {
+            sender: "sender".to_string(),
+            token_id: token_id.to_string(),
+            msg: Some(to_binary(&OwnerOfResponse { owner: "sender".to_string(), approvals: vec![] }).unwrap()),
+        };
+
+        // exceeds the ceiling of the redeem side
+        deps.querier.add_nft("contract1", "token1", MOCK_CONTRACT_ADDR, coin(101, "umed"));
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token1"));
+        match res.unwrap_err() {
+            ContractError::PriceExceedsCeiling { price, ceiling } => {
+                assert_eq!(coin(101, "umed"), price);
+                assert_eq!(coin(100, "umed"), ceiling);
+            }
+            e => panic!("unexpected error: {:?}", e),
         }
-        assert_eq!("action", response.attributes[0].key);
-        assert_eq!("refund", response.attributes[0].value);
-        assert_eq!("sender_contract", response.attributes[1].key);
-        assert_eq!("creator", response.attributes[1].value);
-        assert_eq!("refund", response.attributes[2].key);
+
+        // exceeds the price cap of the source contract
+        deps.querier.price_caps.insert("contract1".to_string(), vec![coin(50, "umed")]);
+        assert_eq!(Some(vec![coin(50, "umed")]), query_price_ceilings(deps.as_ref(), "contract1".to_string()).unwrap().price_caps);
+        deps.querier.add_nft("contract1", "token2", MOCK_CONTRACT_ADDR, coin(60, "umed"));
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token2"));
+        match res.unwrap_err() {
+            ContractError::PriceExceedsCeiling { ceiling, .. } => assert_eq!(coin(50, "umed"), ceiling),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        deps.querier.add_nft("contract1", "token3", MOCK_CONTRACT_ADDR, coin(50, "umed"));
+        let res = execute_receive_nft(deps.as_mut(), env.clone(), mock_info("contract1", &[]), receive_msg("token3"));
+        assert!(res.is_ok());
     }
-}
+
+    #[test]
+    fn redeem_batch() {
+        let mut deps = mock_dependencies_with_nfts(&[]);
+        let env = mock_env();
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            source_contracts: vec!["contract1".to_string()],
+            wind_down_grace_seconds: None,
+            iou_enabled: None,
+            redeem_start: None,
+            redeem_end: None,
+            receipt_contract: None,
+            accepted_denoms: None,
+        };
+
+        let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert!(res.is_ok());
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(250, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+
+        deps.querier.add_nft("contract1", "token1", "sender", coin(100, "umed"));
+        deps.querier.add_nft("contract1", "token2", "sender", coin(100, "umed"));
+        deps.querier.add_nft("contract1", "token3", "sender", coin(100, "umed"));
+        deps.querier.add_nft("contract1", "token4", "other", coin(100, "umed"));
+        let token_ids = |token_ids: &[&str]| token_ids.iter().map(|token_id| token_id.to_string()).collect::<Vec<String>>();
+
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract2".to_string(), token_ids(&["token1"]));
+        match res.unwrap_err() {
+            ContractError::Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token1", "token1"]));
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token1", "token4"]));
+        match res.unwrap_err() {
+            ContractError::Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), vec![]);
+        match res.unwrap_err() {
+            ContractError::InvalidParameter { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // no batch is redeemed while paused or outside the redeem window
+        assert!(execute_pause(deps.as_mut(), env.clone(), info.clone()).is_ok());
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token1"]));
+        match res.unwrap_err() {
+            ContractError::Paused { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert!(execute_unpause(deps.as_mut(), env.clone(), info.clone()).is_ok());
+        let redeem_window = RedeemWindow { redeem_start: Some(env.block.time.plus_seconds(60)), redeem_end: None };
+        assert!(execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), Some("contract1".to_string()), redeem_window).is_ok());
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token1"]));
+        match res.unwrap_err() {
+            ContractError::OutsideRedeemWindow { time } => assert_eq!(env.block.time, time),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let redeem_window = RedeemWindow { redeem_start: None, redeem_end: None };
+        assert!(execute_set_redeem_window(deps.as_mut(), env.clone(), info.clone(), Some("contract1".to_string()), redeem_window).is_ok());
+
+        deps.querier.base.update_balance(MOCK_CONTRACT_ADDR, vec![coin(250, "umed")]);
+        let response = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token1", "token2"])).unwrap();
+        assert_eq!(3, response.messages.len());
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "sender".to_string(), amount: vec![coin(200, "umed")] }), response.messages[0]);
+        for (i, token_id) in ["token1", "token2"].iter().enumerate() {
+            let transfer_msg = cw721_base::msg::ExecuteMsg::TransferNft { recipient: "creator".to_string(), token_id: token_id.to_string() };
+            assert_eq!(CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "contract1".to_string(), msg: to_binary(&transfer_msg).unwrap(), send: vec![] }), response.messages[i + 1]);
+        }
+        assert_eq!(2, response.attributes.iter().filter(|attr| attr.key == "token_id").count());
+        assert_eq!(vec![coin(50, "umed")], query_budget(deps.as_ref(), "contract1".to_string()).unwrap().amount);
+
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token3"]));
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the balance covers only one token because the payouts are sent after the batch
+        let res = execute_deposit(deps.as_mut(), env.clone(), mock_info("creator", &[coin(200, "umed")]), Some("contract1".to_string()));
+        assert!(res.is_ok());
+        deps.querier.base.update_balance(MOCK_CONTRACT_ADDR, vec![coin(150, "umed")]);
+        deps.querier.add_nft("contract1", "token5", "sender", coin(100, "umed"));
+        let res = execute_redeem_batch(deps.as_mut(), env.clone(), mock_info("sender", &[]), "contract1".to_string(), token_ids(&["token3", "token5"]));
+        match res.unwrap_err() {
+            ContractError::InsufficientDeposit {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+}
\ No newline at end of file
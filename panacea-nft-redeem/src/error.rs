@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, Decimal, StdError, Timestamp, VerificationError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +6,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Verification(#[from] VerificationError),
+
     #[error("Unauthorized. {msg:?}")]
     Unauthorized {
         msg: String,
@@ -16,9 +19,63 @@ pub enum ContractError {
         msg: String,
     },
 
+    #[error("UnacceptedDenom. denom: {denom:?}")]
+    UnacceptedDenom {
+        denom: String,
+    },
+
+    #[error("InvalidSignature")]
+    InvalidSignature {},
+
+    #[error("VoucherExpired")]
+    VoucherExpired {},
+
+    #[error("NonceUsed. nonce: {nonce}")]
+    NonceUsed {
+        nonce: u64,
+    },
+
+    #[error("PriceExceedsCeiling. price: {price}, ceiling: {ceiling}")]
+    PriceExceedsCeiling {
+        price: Coin,
+        ceiling: Coin,
+    },
+
     #[error("InsufficientDeposit")]
     InsufficientDeposit {},
 
     #[error("UnmatchedPayer")]
     UnmatchedPayer {},
+
+    #[error("NothingToRefund")]
+    NothingToRefund {},
+
+    #[error("NoOutstandingClaims")]
+    NoOutstandingClaims {},
+
+    #[error("NoExchangeRate. from_denom: {from_denom:?}, to_denom: {to_denom:?}")]
+    NoExchangeRate {
+        from_denom: String,
+        to_denom: String,
+    },
+
+    #[error("ExchangeRateOutOfBounds. rate: {rate}")]
+    ExchangeRateOutOfBounds {
+        rate: Decimal,
+    },
+
+    #[error("OutsideRedeemWindow. time: {time}")]
+    OutsideRedeemWindow {
+        time: Timestamp,
+    },
+
+    #[error("Paused. {reason:?}")]
+    Paused {
+        reason: String,
+    },
+
+    #[error("RateLimitExceeded. {msg:?}")]
+    RateLimitExceeded {
+        msg: String,
+    },
 }
\ No newline at end of file
@@ -1,21 +1,122 @@
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Binary, Coin, Decimal, Timestamp};
+use cw20::Cw20ReceiveMsg;
 use cw721::Cw721ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::types::{Asset, CircuitBreaker, FeeConfig, RateLimits, Receipt, Voucher};
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct InstantiateMsg {
     pub source_contracts: Vec<String>,
+    /// Seconds between `WindDown` and the full refund. Default is 30 days.
+    pub wind_down_grace_seconds: Option<u64>,
+    /// If true, a redemption short of deposit pays what is available and records the rest as a claim.
+    pub iou_enabled: Option<bool>,
+    /// Time from which the tokens can be redeemed.
+    pub redeem_start: Option<Timestamp>,
+    /// Time until which the tokens can be redeemed.
+    pub redeem_end: Option<Timestamp>,
+    /// panacea-nft contract minting a receipt for each redemption. This contract must be its minter.
+    pub receipt_contract: Option<String>,
+    /// Denoms that can be deposited. The denom of a CW20 token is its contract address.
+    /// All denoms are accepted if not set.
+    pub accepted_denoms: Option<Vec<String>>,
 }
 
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Deposit {},
+    /// Deposit funds into the budget of `source_contract`.
+    /// If `source_contract` is omitted, the funds are kept as unallocated.
+    Deposit { source_contract: Option<String> },
+    /// Move unallocated funds into the budget of `source_contract`. Only the payer can allocate.
+    Allocate { source_contract: String, amount: Coin },
     ReceiveNft(Cw721ReceiveMsg),
+    /// Redeem the tokens of `contract` owned by the sender, paying the payouts in one transfer per denom.
+    /// The sender must approve this contract as an operator before. The tokens are transferred to the payer.
+    RedeemBatch { contract: String, token_ids: Vec<String> },
+    /// Deposit CW20 tokens. The `msg` of `Cw20ReceiveMsg` must be a `ReceiveMsg`.
+    /// The CW20 contract must be registered by `RegisterCw20Tokens` or listed in the accepted denoms.
+    Receive(Cw20ReceiveMsg),
     RecoverOwner {contract: String, token_id: String},
+    /// Refund the share of the payer in the funds that are not reserved for unredeemed tokens.
+    /// After the grace period of `WindDown`, all funds are refunded to the depositors pro-rata.
     Refund { },
+    /// Withdraw the share of the sender in the funds that are not reserved for unredeemed tokens.
+    Withdraw {},
+    /// Start the grace period before the full refund. Only the payer can wind down.
+    /// The full refund pays the outstanding claims first, up to the budgets they are queued on.
+    WindDown {},
+    /// Stop winding down, so that the funds are reserved for the unredeemed tokens again.
+    /// Only the payer can cancel the wind down.
+    CancelWindDown {},
+    /// Pay the outstanding claims of the sender, after the earlier claims on the same budget.
+    /// The payouts count to the rate limits and the circuit breaker, and the claims over them are left for later.
+    ClaimOutstanding {},
+    /// Pay the tokens priced in `from_denom` in `to_denom`. Only the payer can set conversions.
+    /// If `rate` is not set, the rate of the oracle is used within [`min_rate`, `max_rate`].
+    SetConversion {
+        from_denom: String,
+        to_denom: String,
+        rate: Option<Decimal>,
+        min_rate: Option<Decimal>,
+        max_rate: Option<Decimal>,
+    },
+    RemoveConversion { from_denom: String },
+    /// Set the oracle contract for the exchange rates. Only the payer can set the oracle.
+    SetOracle { oracle: Option<String> },
+    /// Set the fee taken from each payout, or remove it if `fee_config` is not set.
+    /// Only the payer can update the fee config.
+    UpdateFeeConfig { fee_config: Option<FeeConfig> },
+    /// Set the caps on the redemptions, or remove them if `rate_limits` is not set.
+    /// Only the payer can update the rate limits.
+    UpdateRateLimits { rate_limits: Option<RateLimits> },
+    /// Set the redeem window of `source_contract`, or of the contract if `source_contract` is not set.
+    /// The tokens received outside the window are sent back. Only the payer can set redeem windows.
+    SetRedeemWindow {
+        source_contract: Option<String>,
+        redeem_start: Option<Timestamp>,
+        redeem_end: Option<Timestamp>,
+    },
+    /// Stop the payouts. The tokens received while paused are sent back. Only the payer can pause.
+    Pause {},
+    /// Resume the payouts, also after the circuit breaker tripped. Only the payer can unpause.
+    Unpause {},
+    /// Set the circuit breaker, or remove it if `circuit_breaker` is not set.
+    /// Only the payer can update the circuit breaker.
+    UpdateCircuitBreaker { circuit_breaker: Option<CircuitBreaker> },
+    /// Set the contract minting the receipts, or stop minting if `receipt_contract` is not set.
+    /// Only the payer can set the receipt contract.
+    SetReceiptContract { receipt_contract: Option<String> },
+    /// Set the denoms that can be deposited, or accept all denoms if `accepted_denoms` is not set.
+    /// Only the payer can set the accepted denoms.
+    SetAcceptedDenoms { accepted_denoms: Option<Vec<String>> },
+    /// Allow the deposits of the CW20 tokens `cw20_tokens` besides the accepted denoms.
+    /// Only the payer can register CW20 tokens.
+    RegisterCw20Tokens { cw20_tokens: Vec<String> },
+    /// Send the balances that were not deposited, such as the funds sent directly to this contract, to the payer.
+    /// `cw20_tokens` are the CW20 contracts to check besides the deposited ones. Only the payer can sweep.
+    Sweep { cw20_tokens: Option<Vec<String>> },
+    /// Register the secp256k1 public key of a voucher issuer. Only the payer can register issuers.
+    RegisterIssuer { issuer: String, public_key: Binary },
+    /// Remove a voucher issuer, so that its vouchers cannot be redeemed anymore.
+    /// Only the payer can remove issuers.
+    RemoveIssuer { issuer: String },
+    /// Pay a voucher from the unallocated funds, within the redeem window of the contract.
+    /// The payouts count to the rate limits of the recipient and to the circuit breaker.
+    /// `signature` is the 64 bytes secp256k1 signature of the SHA-256 hash of the voucher in JSON.
+    RedeemVoucher { voucher: Voucher, signature: Binary },
+    /// Set the highest face value of the tokens of `source_contract` that can be redeemed, per denom.
+    /// Only the payer can set price ceilings.
+    SetPriceCeilings { source_contract: String, ceilings: Vec<Coin> },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    Deposit { source_contract: Option<String> },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -27,3 +128,56 @@ pub struct TokenInfoMsg {
     pub sender: String,
 }
 
+
+
+/// Queries of the source contract (panacea-nft) used by this contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceQueryMsg {
+    FaceValueSupply {},
+    MintPolicy {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FaceValueSupplyResponse {
+    pub amount: Vec<Coin>,
+    /// False if the tokens minted before the supply was tracked are not counted.
+    #[serde(default)]
+    pub initialized: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MintPolicyResponse {
+    pub minter: String,
+    pub price_caps: Vec<Coin>,
+}
+
+/// Messages of the receipt contract (panacea-nft) used by this contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptExecuteMsg {
+    Mint(ReceiptMintMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptMintMsg {
+    pub owner: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub price: Asset,
+    pub receipt: Option<Receipt>,
+}
+
+/// Queries of the oracle contract used by this contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleQueryMsg {
+    ExchangeRate { base_denom: String, quote_denom: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExchangeRateResponse {
+    /// Amount of `quote_denom` for 1 `base_denom`
+    pub rate: Decimal,
+}
\ No newline at end of file
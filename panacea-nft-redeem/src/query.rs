@@ -1,14 +1,143 @@
+use cosmwasm_std::{Binary, Coin, Decimal, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::types::{CircuitBreaker, Claim, Conversion, FeeConfig, PauseInfo, RateLimits, RedeemWindow};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     ContractInfo {},
+    Budget { source_contract: String },
+    Unallocated {},
+    Reserves {},
+    /// Claims of `sender` in order of redemption, after the claim id `start_after`
+    Claims { sender: String, start_after: Option<u64>, limit: Option<u32> },
+    Conversion { from_denom: String },
+    FeeConfig {},
+    Limits { sender: String },
+    /// Redeem window applied to the tokens of `source_contract`
+    RedeemWindow { source_contract: String },
+    Status {},
+    Depositors {},
+    Issuers {},
+    PriceCeilings { source_contract: String },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ContractInfoResponse {
     pub source_contracts: Vec<String>,
     pub payer: String,
+    pub wind_down_grace_seconds: u64,
+    pub iou_enabled: bool,
+    pub redeem_start: Option<Timestamp>,
+    pub redeem_end: Option<Timestamp>,
+    pub receipt_contract: Option<String>,
+    pub accepted_denoms: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BudgetResponse {
+    pub source_contract: String,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnallocatedResponse {
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReservesResponse {
+    /// Face value of the tokens not redeemed yet
+    pub liabilities: Vec<Coin>,
+    /// Funds that can be refunded before the wind-down
+    pub excess: Vec<Coin>,
+    /// Time from which all funds can be refunded
+    pub refundable_at: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimsResponse {
+    pub claims: Vec<Claim>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConversionResponse {
+    pub conversion: Conversion,
+    pub oracle: Option<String>,
+    /// Rate applied to the redemptions now
+    pub rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeConfigResponse {
+    pub fee_config: Option<FeeConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitsResponse {
+    pub rate_limits: Option<RateLimits>,
+    /// Redemptions the sender can make in the current day
+    pub remaining_redemptions: Option<u32>,
+    /// Payout the sender can receive in the current window
+    pub remaining_payout: Vec<Coin>,
+    /// Payout left in the current block
+    pub remaining_block_payout: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedeemWindowResponse {
+    pub source_contract: String,
+    pub redeem_window: RedeemWindow,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatusResponse {
+    pub paused: Option<PauseInfo>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Payouts within the window of the circuit breaker
+    pub window_payout: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DepositorsResponse {
+    pub depositors: Vec<DepositorInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DepositorInfo {
+    pub depositor: String,
+    pub contributions: Vec<ContributionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContributionInfo {
+    pub denom: String,
+    /// Total amount deposited
+    pub deposited: Uint128,
+    pub shares: Uint128,
+    /// Share of the remaining funds
+    pub value: Uint128,
+    /// Share of the funds that can be withdrawn now
+    pub withdrawable: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IssuersResponse {
+    pub issuers: Vec<IssuerInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IssuerInfo {
+    pub issuer: String,
+    pub public_key: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceCeilingsResponse {
+    pub source_contract: String,
+    pub ceilings: Vec<Coin>,
+    /// Price caps of the source contract, if it provides its mint policy
+    pub price_caps: Option<Vec<Coin>>,
 }
\ No newline at end of file
@@ -1,7 +1,66 @@
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Binary, Coin, Empty, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map, U64Key};
 
 use crate::query::ContractInfoResponse;
-use crate::types::TokenOwnerInfo;
+use crate::types::{BlockPayout, CircuitBreaker, Claim, Contribution, Conversion, FeeConfig, PauseInfo, RateLimits, RateUsage, RedeemWindow, TokenOwnerInfo};
 
 pub const CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("contract_info");
-pub const TOKEN_OWNER_INFO: Map<(String, String), TokenOwnerInfo> = Map::new("token_info");
\ No newline at end of file
+pub const TOKEN_OWNER_INFO: Map<(String, String), TokenOwnerInfo> = Map::new("token_info");
+/// CW20 contracts that can be deposited to this contract, registered by the payer or listed in the accepted denoms.
+/// The funds in CW20 tokens are accounted with the contract address as the denom.
+pub const CW20_TOKENS: Map<&str, Empty> = Map::new("cw20_tokens");
+/// Funds earmarked for each source contract, keyed by (source_contract, denom).
+pub const BUDGETS: Map<(&str, &str), Coin> = Map::new("budgets");
+/// Funds deposited without a source contract, keyed by denom.
+pub const UNALLOCATED: Map<&str, Coin> = Map::new("unallocated");
+/// Addresses that have deposited funds.
+pub const DEPOSITORS: Map<&str, Empty> = Map::new("depositors");
+/// Contributions of the depositors, keyed by (depositor, denom).
+pub const CONTRIBUTIONS: Map<(&str, &str), Contribution> = Map::new("contributions");
+/// Sum of the shares of all depositors, keyed by denom.
+pub const TOTAL_SHARES: Map<&str, Uint128> = Map::new("total_shares");
+
+/// Face value of the redeemed tokens, keyed by (source_contract, denom).
+pub const REDEEMED: Map<(&str, &str), Coin> = Map::new("redeemed");
+/// Time from which the payer can refund all funds. Set by `WindDown`.
+pub const REFUNDABLE_AT: Item<Timestamp> = Item::new("refundable_at");
+
+/// Unpaid redemptions in IOU mode, keyed by claim id in order of redemption time.
+pub const CLAIMS: Map<U64Key, Claim> = Map::new("claims");
+pub const CLAIM_SEQ: Item<u64> = Item::new("claim_seq");
+/// Outstanding amounts of the claims queued on each budget, keyed by ((source_contract, denom), claim id).
+pub const CLAIM_QUEUES: Map<((&str, &str), U64Key), Uint128> = Map::new("claim_queues");
+/// Claims of each sender, keyed by (sender, claim id).
+pub const SENDER_CLAIMS: Map<(&str, U64Key), Empty> = Map::new("sender_claims");
+/// Sum of the unpaid claims, keyed by (source_contract, denom).
+pub const OUTSTANDING: Map<(&str, &str), Coin> = Map::new("outstanding");
+
+/// Conversions of the prices, keyed by the denom of the price.
+pub const CONVERSIONS: Map<&str, Conversion> = Map::new("conversions");
+/// Contract answering `OracleQueryMsg::ExchangeRate`.
+pub const ORACLE: Item<String> = Item::new("oracle");
+/// Fee taken from the payouts.
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
+
+pub const RATE_LIMITS: Item<RateLimits> = Item::new("rate_limits");
+/// Redemptions and payouts of each sender in the current day and window of `RATE_LIMITS`, keyed by sender.
+pub const RATE_USAGE: Map<&str, RateUsage> = Map::new("rate_usage");
+/// Payouts of the latest block with a redemption.
+pub const BLOCK_PAYOUT: Item<BlockPayout> = Item::new("block_payout");
+
+/// Redeem windows of the source contracts, overriding the window of the contract info.
+pub const REDEEM_WINDOWS: Map<&str, RedeemWindow> = Map::new("redeem_windows");
+
+/// Set while the payouts are stopped, by `Pause` or by the circuit breaker.
+pub const PAUSED: Item<PauseInfo> = Item::new("paused");
+pub const CIRCUIT_BREAKER: Item<CircuitBreaker> = Item::new("circuit_breaker");
+/// Payouts of all senders in each time slot of the window of `CIRCUIT_BREAKER`, keyed by slot since the Unix epoch.
+pub const PAYOUT_SLOTS: Map<U64Key, Vec<Coin>> = Map::new("payout_slots");
+
+/// secp256k1 public keys of the voucher issuers, keyed by issuer.
+pub const ISSUERS: Map<&str, Binary> = Map::new("issuers");
+/// Nonces of the redeemed vouchers, keyed by (issuer, nonce).
+pub const USED_NONCES: Map<(&str, U64Key), Empty> = Map::new("used_nonces");
+
+/// Highest face value of the tokens that can be redeemed, keyed by source contract.
+pub const PRICE_CEILINGS: Map<&str, Vec<Coin>> = Map::new("price_ceilings");
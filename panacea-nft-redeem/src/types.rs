@@ -1,4 +1,5 @@
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Coin, Decimal, Timestamp, Uint128};
+use cw20::Cw20Coin;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use cw721::OwnerOfResponse;
@@ -11,11 +12,161 @@ pub struct ContractInfo {
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct TokenInfo {
+    pub price: Asset,
+}
+
+/// Token info of the NFTs minted before the CW20 prices were supported.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LegacyTokenInfo {
     pub price: Coin,
 }
 
+/// A native coin or a CW20 token, serialized as the `Asset` of panacea-nft.
+/// Kept in this crate so that it builds on its own with the optimizer.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Asset {
+    Native(Coin),
+    Cw20(Cw20Coin),
+}
+
+impl Asset {
+    /// Returns the asset as a coin. The denom of a CW20 token is its contract address.
+    pub fn to_coin(&self) -> Coin {
+        match self {
+            Asset::Native(coin) => coin.clone(),
+            Asset::Cw20(cw20_coin) => Coin { denom: cw20_coin.address.to_string(), amount: cw20_coin.amount },
+        }
+    }
+}
+
+impl From<Coin> for Asset {
+    fn from(coin: Coin) -> Self {
+        Asset::Native(coin)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct TokenOwnerInfo {
     pub sender: String,
     pub owner_of: OwnerOfResponse,
+}
+
+/// The unpaid part of a redemption. Claims are paid in order of redemption time.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Claim {
+    pub id: u64,
+    pub sender: String,
+    pub source_contract: String,
+    pub token_id: String,
+    pub outstanding: Coin,
+    pub redeemed_at: Timestamp,
+}
+
+/// Conversion of the prices in `from_denom` into the payouts in `to_denom`.
+/// The rate is `rate` if set, otherwise the rate of the oracle contract.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Conversion {
+    pub from_denom: String,
+    pub to_denom: String,
+    pub rate: Option<Decimal>,
+    pub min_rate: Option<Decimal>,
+    pub max_rate: Option<Decimal>,
+}
+
+/// Fee taken from each payout and sent to `treasury`.
+/// The fee is `percent` of the payout plus the `flat` fee in the denom of the payout.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FeeConfig {
+    pub treasury: String,
+    pub percent: Decimal,
+    pub flat: Vec<Coin>,
+}
+
+/// Caps on the redemptions of each sender and on the payouts of each block.
+/// The caps that are not set, and the denoms not listed, are not limited.
+/// The days and the windows are fixed periods counted from the Unix epoch.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RateLimits {
+    pub max_redemptions_per_day: Option<u32>,
+    pub max_payout_per_window: Vec<Coin>,
+    pub window_seconds: u64,
+    pub max_payout_per_block: Vec<Coin>,
+}
+
+/// Redemptions of a sender in the current day and payouts in the current window of the rate limits.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RateUsage {
+    /// Days since the Unix epoch
+    pub day: u64,
+    pub redemptions: u32,
+    /// Windows of `window_seconds` since the Unix epoch
+    pub window: u64,
+    pub payout: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BlockPayout {
+    pub height: u64,
+    pub amount: Vec<Coin>,
+}
+
+/// Time window in which the tokens can be redeemed. `redeem_end` is exclusive.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RedeemWindow {
+    pub redeem_start: Option<Timestamp>,
+    pub redeem_end: Option<Timestamp>,
+}
+
+impl RedeemWindow {
+    pub fn contains(&self, time: Timestamp) -> bool {
+        self.redeem_start.map_or(true, |start| time >= start)
+            && self.redeem_end.map_or(true, |end| time < end)
+    }
+}
+
+/// Stops the payouts when the payouts within `window_seconds` exceed the `threshold` of the denom.
+/// The payouts are summed in slots of a tenth of the window, so the window ends at the start of the current slot.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CircuitBreaker {
+    pub threshold: Vec<Coin>,
+    pub window_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PauseInfo {
+    pub reason: String,
+    pub paused_at: Timestamp,
+}
+
+/// Proof of a redemption, minted on the receipt contract to the sender.
+/// Serialized as the `Receipt` of panacea-nft.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Receipt {
+    pub source_contract: String,
+    pub token_id: String,
+    pub paid: Coin,
+    pub redeemed_at: Timestamp,
+}
+
+/// Contribution of a depositor in a denom.
+/// The funds are owned pro-rata to `shares`, which lose value as the funds are paid out.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Contribution {
+    /// Total amount deposited
+    pub deposited: Uint128,
+    pub shares: Uint128,
+}
+
+/// Payout to `recipient` signed off-chain by the key of `issuer`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Voucher {
+    /// The redeem contract, so the voucher cannot be redeemed on another contract
+    pub contract: String,
+    pub issuer: String,
+    pub recipient: String,
+    pub amount: Coin,
+    /// Unique per issuer
+    pub nonce: u64,
+    pub expiry: Timestamp,
 }
\ No newline at end of file
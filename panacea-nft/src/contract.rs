@@ -1,11 +1,12 @@
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, from_binary, MessageInfo, Response, StdResult, to_binary, to_vec};
+use cosmwasm_std::{attr, Binary, Coin, Deps, DepsMut, Env, from_binary, MessageInfo, Order, Response, StdError, StdResult, to_binary, to_vec};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cw721::{NumTokensResponse, OwnerOfResponse};
+use cw721_base::msg::MinterResponse;
 use cw721_base::ContractError;
-use cw721_base::msg::QueryMsg;
 
-use crate::{ExecuteMsg, InstantiateMsg, MintMsg, TokenInfo};
+use crate::{Asset, ExecuteMsg, FaceValueSupplyResponse, InstantiateMsg, MigrateMsg, MintMsg, MintPolicyResponse, QueryMsg, TokenInfo};
+use crate::state::{FACE_VALUE_SUPPLY, FACE_VALUE_SUPPLY_INITIALIZED, PRICE_CAPS};
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -14,9 +15,40 @@ pub fn instantiate(
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
+    FACE_VALUE_SUPPLY_INITIALIZED.save(deps.storage, &true)?;
     cw721_base::contract::instantiate(deps, env, info, msg.into_cw721_instantiate_msg())
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> StdResult<Response> {
+    let face_value_supply = match msg.face_value_supply {
+        Some(face_value_supply) => face_value_supply,
+        None => return Ok(Response::default()),
+    };
+    if FACE_VALUE_SUPPLY_INITIALIZED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(StdError::generic_err("face value supply is already initialized"));
+    }
+
+    for price in face_value_supply.iter().map(|asset| asset.to_coin()) {
+        FACE_VALUE_SUPPLY.update(deps.storage, price.denom.as_str(), |supply| -> StdResult<Coin> {
+            let mut supply = supply.unwrap_or_else(|| Coin::new(0, price.denom.clone()));
+            supply.amount = supply.amount.checked_add(price.amount)?;
+            Ok(supply)
+        })?;
+    }
+    FACE_VALUE_SUPPLY_INITIALIZED.save(deps.storage, &true)?;
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "migrate"),
+            attr("face_value_supply", face_value_supply.iter().map(|asset| asset.to_coin().to_string()).collect::<Vec<String>>().join(", ")),
+        ],
+        data: None,
+    })
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -27,28 +59,74 @@ pub fn execute(
     match msg {
         ExecuteMsg::Mint(msg) => execute_mint(deps, env, info, msg),
         ExecuteMsg::SendNft { contract, token_id } => execute_send_nft(deps, env, info, contract, token_id),
+        ExecuteMsg::SetPriceCaps { price_caps } => execute_set_price_caps(deps, env, info, price_caps),
         _ => cw721_base::contract::execute(deps, env, info, msg.into_cw721_execute_msg()),
     }
 }
 
-fn execute_mint(deps: DepsMut, env: Env, info: MessageInfo, mut msg: MintMsg) -> Result<Response, ContractError> {
-    let contract_info: cw721::ContractInfoResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), QueryMsg::ContractInfo {})?)?;
+fn execute_mint(mut deps: DepsMut, env: Env, info: MessageInfo, mut msg: MintMsg) -> Result<Response, ContractError> {
+    let contract_info: cw721::ContractInfoResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), cw721_base::msg::QueryMsg::ContractInfo {})?)?;
     let symbol = contract_info.symbol;
-    let num_token_res: NumTokensResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), QueryMsg::NumTokens {})?)?;
+    let num_token_res: NumTokensResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), cw721_base::msg::QueryMsg::NumTokens {})?)?;
     let next_index = num_token_res.count + 1;
 
     let token_id: String = [symbol, next_index.to_string()].join(".");
 
     let token_info = TokenInfo {
         price: msg.price.clone(),
+        receipt: msg.receipt.clone(),
     };
     msg.description = Some(String::from_utf8(to_vec(&token_info).unwrap()).unwrap());
 
-    cw721_base::contract::execute_mint(deps, env, info, msg.into_cw721_mint_msg(token_id))
+    let price = msg.price.to_coin();
+    if let Some(price_cap) = PRICE_CAPS.may_load(deps.storage, price.denom.as_str())? {
+        if price.amount > price_cap.amount {
+            return Err(ContractError::Std(StdError::generic_err(format!("price {} exceeds the cap {}", price, price_cap))));
+        }
+    }
+    let res = cw721_base::contract::execute_mint(deps.branch(), env, info, msg.into_cw721_mint_msg(token_id))?;
+
+    FACE_VALUE_SUPPLY.update(deps.storage, price.denom.as_str(), |supply| -> StdResult<Coin> {
+        let mut supply = supply.unwrap_or_else(|| Coin::new(0, price.denom.clone()));
+        supply.amount = supply.amount.checked_add(price.amount)?;
+        Ok(supply)
+    })?;
+
+    Ok(res)
+}
+
+fn execute_set_price_caps(deps: DepsMut, env: Env, info: MessageInfo, price_caps: Vec<Asset>) -> Result<Response, ContractError> {
+    let minter: MinterResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env, cw721_base::msg::QueryMsg::Minter {})?)?;
+    if info.sender.as_str().ne(minter.minter.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let denoms = PRICE_CAPS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(String::from_utf8)
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(StdError::from)?;
+    for denom in denoms {
+        PRICE_CAPS.remove(deps.storage, denom.as_str());
+    }
+    let price_caps: Vec<Coin> = price_caps.iter().map(|price_cap| price_cap.to_coin()).collect();
+    for price_cap in price_caps.iter() {
+        PRICE_CAPS.save(deps.storage, price_cap.denom.as_str(), price_cap)?;
+    }
+
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        attributes: vec![
+            attr("action", "set_price_caps"),
+            attr("price_caps", price_caps.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+        ],
+        data: None,
+    })
 }
 
 fn execute_send_nft(deps: DepsMut, env: Env, info: MessageInfo, contract: String, token_id: String) -> Result<Response, ContractError> {
-    let owner_of: OwnerOfResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), QueryMsg::OwnerOf {
+    let owner_of: OwnerOfResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), cw721_base::msg::QueryMsg::OwnerOf {
         token_id: token_id.to_string(),
         include_expired: None,
     })?)?;
@@ -58,21 +136,44 @@ fn execute_send_nft(deps: DepsMut, env: Env, info: MessageInfo, contract: String
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    cw721_base::contract::query(deps, env, msg)
+    match msg {
+        QueryMsg::FaceValueSupply {} => to_binary(&query_face_value_supply(deps)?),
+        QueryMsg::MintPolicy {} => to_binary(&query_mint_policy(deps, env)?),
+        _ => cw721_base::contract::query(deps, env, msg.into_cw721_query_msg()?),
+    }
+}
+
+fn query_face_value_supply(deps: Deps) -> StdResult<FaceValueSupplyResponse> {
+    let amount = FACE_VALUE_SUPPLY
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, coin)| coin))
+        .collect::<StdResult<Vec<Coin>>>()?;
+    let initialized = FACE_VALUE_SUPPLY_INITIALIZED.may_load(deps.storage)?.unwrap_or(false);
+    Ok(FaceValueSupplyResponse { amount, initialized })
+}
+
+fn query_mint_policy(deps: Deps, env: Env) -> StdResult<MintPolicyResponse> {
+    let minter: MinterResponse = from_binary(&cw721_base::contract::query(deps, env, cw721_base::msg::QueryMsg::Minter {})?)?;
+    let price_caps = PRICE_CAPS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, coin)| coin))
+        .collect::<StdResult<Vec<Coin>>>()?;
+    Ok(MintPolicyResponse { minter: minter.minter, price_caps })
 }
 
 #[cfg(test)]
 mod tests {
     use std::cmp::min;
 
-    use cosmwasm_std::{attr, coin, CosmosMsg, DepsMut, from_slice, to_vec, WasmMsg};
+    use cosmwasm_std::{attr, coin, CosmosMsg, DepsMut, from_slice, to_vec, Uint128, WasmMsg};
+    use cw20::Cw20Coin;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
     use cw721::{Approval, Cw721ExecuteMsg, Expiration, NftInfoResponse};
     use cw721_base::msg::ExecuteMsg::Approve;
     use cw721_base::msg::InstantiateMsg;
 
     use crate::msg::ReceiverExecuteMsg;
-    use crate::types::TokenInfo;
+    use crate::types::{Asset, Receipt, TokenInfo};
 
     use super::*;
 
@@ -102,7 +203,8 @@ mod tests {
             name: "nft_med_1".to_string(),
             description: Some("No description".to_string()),
             image: None,
-            price: coin(1000000, "umed"),
+            price: coin(1000000, "umed").into(),
+            receipt: None,
         };
 
         let info = mock_info(MINTER, &[]);
@@ -119,10 +221,40 @@ mod tests {
         assert_eq!(attr3.key, "token_id");
         assert_eq!([SYMBOL, "1"].join("."), token_id);
 
-        let nft_info: NftInfoResponse = from_binary(&cw721_base::contract::query(deps.as_ref(), env.clone(), QueryMsg::NftInfo { token_id }).unwrap()).unwrap();
+        let nft_info: NftInfoResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::NftInfo { token_id }).unwrap()).unwrap();
+
+        let token_info: TokenInfo = from_slice(nft_info.description.as_bytes()).unwrap();
+        assert_eq!(Asset::Native(coin(1000000, "umed")), token_info.price);
+        assert_eq!(None, token_info.receipt);
+        assert!(!nft_info.description.contains("receipt"));
+    }
+
+    #[test]
+    fn test_execute_mint_receipt() {
+        let mut deps = mock_dependencies(&[]);
+        setup_contract(deps.as_mut());
+        let env = mock_env();
+
+        let receipt = Receipt {
+            source_contract: "contract1".to_string(),
+            token_id: "N_MED.1".to_string(),
+            paid: coin(1000000, "umed"),
+            redeemed_at: env.block.time,
+        };
+        let mint_msg = MintMsg {
+            owner: "patient".to_string(),
+            name: "receipt".to_string(),
+            description: None,
+            image: None,
+            price: coin(0, "umed").into(),
+            receipt: Some(receipt.clone()),
+        };
+        let res = execute_mint(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), mint_msg);
+        assert!(res.is_ok());
 
+        let nft_info: NftInfoResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::NftInfo { token_id: [SYMBOL, "1"].join(".") }).unwrap()).unwrap();
         let token_info: TokenInfo = from_slice(nft_info.description.as_bytes()).unwrap();
-        assert_eq!(coin(1000000, "umed"), token_info.price);
+        assert_eq!(Some(receipt), token_info.receipt);
     }
 
     #[test]
@@ -136,7 +268,8 @@ mod tests {
             name: "nft_med_1".to_string(),
             description: Some("No description".to_string()),
             image: None,
-            price: coin(1000000, "umed"),
+            price: coin(1000000, "umed").into(),
+            receipt: None,
         };
 
         let info = mock_info(MINTER, &[]);
@@ -178,4 +311,97 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_query_face_value_supply() {
+        let mut deps = mock_dependencies(&[]);
+        setup_contract(deps.as_mut());
+        let env = mock_env();
+
+        let prices = [
+            Asset::Native(coin(1000000, "umed")),
+            Asset::Native(coin(2000000, "umed")),
+            Asset::Native(coin(500, "uatom")),
+            Asset::Cw20(Cw20Coin { address: "cw20_contract".to_string(), amount: Uint128(700) }),
+        ];
+        for price in prices.iter() {
+            let mint_msg = MintMsg {
+                owner: MINTER.to_string(),
+                name: "nft_med".to_string(),
+                description: None,
+                image: None,
+                price: price.clone(),
+                receipt: None,
+            };
+            let res = execute_mint(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), mint_msg);
+            assert!(res.is_ok());
+        }
+
+        let supply: FaceValueSupplyResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::FaceValueSupply {}).unwrap()).unwrap();
+        assert_eq!(vec![coin(700, "cw20_contract"), coin(500, "uatom"), coin(3000000, "umed")], supply.amount);
+    }
+
+    #[test]
+    fn test_migrate_face_value_supply() {
+        let mut deps = mock_dependencies(&[]);
+        // a contract instantiated before the face value supply was tracked
+        setup_contract(deps.as_mut());
+        let env = mock_env();
+
+        let mint_msg = MintMsg {
+            owner: MINTER.to_string(),
+            name: "nft_med".to_string(),
+            description: None,
+            image: None,
+            price: coin(1000, "umed").into(),
+            receipt: None,
+        };
+        let res = execute_mint(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), mint_msg);
+        assert!(res.is_ok());
+
+        let supply: FaceValueSupplyResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::FaceValueSupply {}).unwrap()).unwrap();
+        assert_eq!(FaceValueSupplyResponse { amount: vec![coin(1000, "umed")], initialized: false }, supply);
+
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { face_value_supply: Some(vec![coin(3000, "umed").into(), coin(500, "uatom").into()]) });
+        assert!(res.is_ok());
+        let supply: FaceValueSupplyResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::FaceValueSupply {}).unwrap()).unwrap();
+        assert_eq!(FaceValueSupplyResponse { amount: vec![coin(500, "uatom"), coin(4000, "umed")], initialized: true }, supply);
+
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { face_value_supply: Some(vec![coin(3000, "umed").into()]) });
+        assert!(res.is_err());
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { face_value_supply: None });
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_set_price_caps() {
+        let mut deps = mock_dependencies(&[]);
+        setup_contract(deps.as_mut());
+        let env = mock_env();
+
+        let price_caps = vec![Asset::Native(coin(1000, "umed"))];
+        let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SetPriceCaps { price_caps: price_caps.clone() });
+        assert!(res.is_err());
+        let res = execute(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), ExecuteMsg::SetPriceCaps { price_caps });
+        assert!(res.is_ok());
+
+        let mint_policy: MintPolicyResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::MintPolicy {}).unwrap()).unwrap();
+        assert_eq!(MintPolicyResponse { minter: MINTER.to_string(), price_caps: vec![coin(1000, "umed")] }, mint_policy);
+
+        let mint_msg = |price: Asset| MintMsg {
+            owner: MINTER.to_string(),
+            name: "nft_med".to_string(),
+            description: None,
+            image: None,
+            price,
+            receipt: None,
+        };
+        let res = execute_mint(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), mint_msg(coin(1000, "umed").into()));
+        assert!(res.is_ok());
+        let res = execute_mint(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), mint_msg(coin(1001, "umed").into()));
+        assert!(res.is_err());
+        // the denoms without a cap are not limited
+        let res = execute_mint(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), mint_msg(coin(1001, "uatom").into()));
+        assert!(res.is_ok());
+    }
+}
@@ -2,7 +2,8 @@ mod msg;
 mod contract;
 mod types;
 mod error;
+mod state;
 
 pub use crate::error::ContractError;
-pub use crate::msg::{ExecuteMsg, InstantiateMsg, MintMsg, ReceiverExecuteMsg};
-pub use crate::types::TokenInfo;
\ No newline at end of file
+pub use crate::msg::{ExecuteMsg, FaceValueSupplyResponse, InstantiateMsg, MigrateMsg, MintMsg, MintPolicyResponse, QueryMsg, ReceiverExecuteMsg};
+pub use crate::types::{Asset, Receipt, TokenInfo};
\ No newline at end of file
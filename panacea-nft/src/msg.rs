@@ -1,5 +1,7 @@
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Coin, StdError, StdResult};
 use cw721::{Cw721ReceiveMsg, Expiration};
+
+use crate::types::{Asset, Receipt};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +28,13 @@ impl InstantiateMsg {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MigrateMsg {
+    /// Sum of the prices of the NFTs minted before the face value supply was tracked.
+    /// Can only be set once, on the migration from a contract without the face value supply.
+    pub face_value_supply: Option<Vec<Asset>>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
@@ -57,6 +66,9 @@ pub enum ExecuteMsg {
 
     /// Mint a new NFT, can only be called by the contract minter
     Mint(MintMsg),
+    /// Replace the highest prices of the NFTs that can be minted, per denom.
+    /// The denoms without a cap are not limited. Can only be called by the contract minter
+    SetPriceCaps { price_caps: Vec<Asset> },
 }
 
 impl ExecuteMsg {
@@ -82,7 +94,9 @@ pub struct MintMsg {
     /// A URI pointing to an image representing the asset
     pub image: Option<String>,
 
-    pub price: Coin,
+    pub price: Asset,
+    /// Set if the NFT is a receipt of a redemption
+    pub receipt: Option<Receipt>,
 }
 
 impl MintMsg {
@@ -97,6 +111,78 @@ impl MintMsg {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Return the owner of the given token, error if token does not exist
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    /// List all operators that can access all of the owner's tokens
+    ApprovedForAll {
+        owner: String,
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Total number of tokens issued
+    NumTokens {},
+    ContractInfo {},
+    NftInfo { token_id: String },
+    AllNftInfo {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    Minter {},
+
+    /// Sum of the prices of all minted NFTs per denom.
+    /// The denom of a CW20 token is its contract address.
+    FaceValueSupply {},
+    /// The minter and the price caps of the minted NFTs.
+    MintPolicy {},
+}
+
+impl QueryMsg {
+    /// Returns the query of cw721-base, or an error for the queries answered by this contract.
+    pub fn into_cw721_query_msg(self) -> StdResult<cw721_base::msg::QueryMsg> {
+        match self {
+            QueryMsg::OwnerOf { token_id, include_expired } => Ok(cw721_base::msg::QueryMsg::OwnerOf { token_id, include_expired }),
+            QueryMsg::ApprovedForAll { owner, include_expired, start_after, limit } => Ok(cw721_base::msg::QueryMsg::ApprovedForAll { owner, include_expired, start_after, limit }),
+            QueryMsg::NumTokens {} => Ok(cw721_base::msg::QueryMsg::NumTokens {}),
+            QueryMsg::ContractInfo {} => Ok(cw721_base::msg::QueryMsg::ContractInfo {}),
+            QueryMsg::NftInfo { token_id } => Ok(cw721_base::msg::QueryMsg::NftInfo { token_id }),
+            QueryMsg::AllNftInfo { token_id, include_expired } => Ok(cw721_base::msg::QueryMsg::AllNftInfo { token_id, include_expired }),
+            QueryMsg::Tokens { owner, start_after, limit } => Ok(cw721_base::msg::QueryMsg::Tokens { owner, start_after, limit }),
+            QueryMsg::AllTokens { start_after, limit } => Ok(cw721_base::msg::QueryMsg::AllTokens { start_after, limit }),
+            QueryMsg::Minter {} => Ok(cw721_base::msg::QueryMsg::Minter {}),
+            QueryMsg::FaceValueSupply {} | QueryMsg::MintPolicy {} => Err(StdError::generic_err("not a cw721-base query"))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FaceValueSupplyResponse {
+    pub amount: Vec<Coin>,
+    /// False if the NFTs minted before the supply was tracked are not counted, so `amount` is only a lower bound.
+    pub initialized: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MintPolicyResponse {
+    pub minter: String,
+    pub price_caps: Vec<Coin>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum ReceiverExecuteMsg {
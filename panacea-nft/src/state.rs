@@ -0,0 +1,10 @@
+use cosmwasm_std::Coin;
+use cw_storage_plus::{Item, Map};
+
+/// Sum of the prices of all minted NFTs, keyed by denom or CW20 contract address.
+pub const FACE_VALUE_SUPPLY: Map<&str, Coin> = Map::new("face_value_supply");
+/// Set if `FACE_VALUE_SUPPLY` counts all minted NFTs, from the instantiation or since the migration setting
+/// the supply of the NFTs minted before it was tracked.
+pub const FACE_VALUE_SUPPLY_INITIALIZED: Item<bool> = Item::new("face_value_supply_initialized");
+/// Highest price of the NFTs that can be minted, keyed by denom or CW20 contract address.
+pub const PRICE_CAPS: Map<&str, Coin> = Map::new("price_caps");
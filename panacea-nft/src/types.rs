@@ -1,8 +1,44 @@
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Coin, Timestamp};
+use cw20::Cw20Coin;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct TokenInfo {
-    pub price: Coin,
+    pub price: Asset,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Receipt>,
+}
+
+/// A native coin or a CW20 token.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Asset {
+    Native(Coin),
+    Cw20(Cw20Coin),
+}
+
+impl Asset {
+    /// Returns the asset as a coin. The denom of a CW20 token is its contract address.
+    pub fn to_coin(&self) -> Coin {
+        match self {
+            Asset::Native(coin) => coin.clone(),
+            Asset::Cw20(cw20_coin) => Coin { denom: cw20_coin.address.to_string(), amount: cw20_coin.amount },
+        }
+    }
+}
+
+impl From<Coin> for Asset {
+    fn from(coin: Coin) -> Self {
+        Asset::Native(coin)
+    }
+}
+
+/// Proof of a redemption of `token_id` of `source_contract`, minted by the redeem contract.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Receipt {
+    pub source_contract: String,
+    pub token_id: String,
+    pub paid: Coin,
+    pub redeemed_at: Timestamp,
 }
\ No newline at end of file
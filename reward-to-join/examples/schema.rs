@@ -1,8 +1,8 @@
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use std::env::current_dir;
 use std::fs::create_dir_all;
-use panacea_smart_contract::msg::{InstantiateMsg, ExecuteMsg, QueryMsg};
-use panacea_smart_contract::state::State;
+use reward_join::msg::{InstantiateMsg, ExecuteMsg, QueryMsg};
+use reward_join::state::State;
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -1,31 +1,71 @@
-use cosmwasm_std::{Addr, attr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response, StdResult, to_binary};
+use cosmwasm_std::{Addr, attr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult, Storage, Timestamp, to_binary, to_vec};
+use sha2::{Digest, Sha256};
 
 use crate::error::ContractError;
-use crate::msg::{AchievementResponse, ExecuteMsg, InstantiateMsg, JoinerResponse, QueryMsg, TermOfPaymentsResponse};
-use crate::state::{Achievement, config, config_read, State};
+use crate::msg::{AchievementResponse, Amendment, AmendmentsResponse, ChallengeResponse, ClaimPayload, DataProvider, DataProviderResponse, Duration, EnrollmentsResponse, ExecuteMsg, InstantiateMsg, JoinerResponse, Metric, MetricKind, MetricSpec, PayoutsResponse, QueryMsg, Reporter, ReportersResponse, ReportsResponse, SignatureScheme, TermOfPayment, TermOfPaymentsResponse};
+use crate::state::{Achievement, AmendmentRecord, Payout, challenge_window, challenge_window_read, ChallengeWindow, config, config_read, data_provider, data_provider_read, enrollments, enrollments_read, PayoutStatus, Program, program, program_read, Report, reporter_set, reporter_set_read, ReporterSet, State, subtract_amounts};
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    let metrics = msg.metrics.unwrap_or_else(default_metrics);
+    // reporters, data provider and challenge are shared by the enrollments of a program
+    if let Some(reporters) = msg.reporters {
+        instantiate_reporters(deps.branch(), &metrics, reporters, msg.quorum.unwrap_or(1))?;
+    }
+    if let Some(provider) = msg.data_provider {
+        let valid = match provider.scheme {
+            SignatureScheme::Secp256k1 => provider.public_key.len() == 33 || provider.public_key.len() == 65,
+            SignatureScheme::Ed25519 => provider.public_key.len() == 32,
+        };
+        if !valid {
+            return Err(ContractError::InvalidDataProvider { msg: format!("invalid {:?} public key", provider.scheme) });
+        }
+        data_provider(deps.storage).save(&provider)?;
+    }
+    if let Some(challenge) = msg.challenge {
+        // without a challenge, a payout is paid immediately
+        if challenge.period.seconds() == Some(0) {
+            return Err(ContractError::InvalidDuration { duration: challenge.period, msg: "zero challenge period".to_string() });
+        }
+        if challenge.period.after(env.block.time).is_none() {
+            return Err(ContractError::InvalidDuration { duration: challenge.period, msg: "overflow".to_string() });
+        }
+        let arbiter = deps.api.addr_validate(&challenge.arbiter)?;
+        challenge_window(deps.storage).save(&ChallengeWindow { period: challenge.period, arbiter })?;
+    }
+    let joiner = match msg.joiner {
+        Some(joiner) => joiner,
+        None => return instantiate_program(deps, info, msg.term_of_payments, metrics),
+    };
+
+    let achievement = Achievement::new(&metrics)?;
+    validate_term_of_payments(&achievement, &msg.term_of_payments, &info.funds, env.block.time, msg.end_time)?;
     let state = State {
         payer: info.sender,
-        joiner: deps.api.addr_validate(&msg.joiner)?,
+        joiner: deps.api.addr_validate(&joiner)?,
         total_amount: info.funds,
         term_of_payments: msg.term_of_payments,
-        achievement: Achievement {
-            treatments: 0,
-            insurance_claim: false,
-        },
-        start_time_millis: Some(env.block.time.nanos() / 1000),
-        end_time_millis: msg.end_time_millis,
+        achievement,
+        start_time: env.block.time,
+        end_time: msg.end_time,
+        pending_amendment: None,
+        amendments: vec![],
+        reports: vec![],
+        accepted_reports: vec![],
+        claim_nonce: 0,
+        payouts: vec![],
     };
 
     if state.is_expired(&env) {
         return Err(ContractError::Expired {
-            end_time: msg.end_time_millis,
+            end_time: msg.end_time,
         });
     }
 
@@ -33,19 +73,288 @@ pub fn instantiate(
     Ok(Response::default())
 }
 
+fn instantiate_program(
+    deps: DepsMut,
+    info: MessageInfo,
+    term_of_payments: Vec<TermOfPayment>,
+    metrics: Vec<MetricSpec>,
+) -> Result<Response, ContractError> {
+    if !term_of_payments.is_empty() || !info.funds.is_empty() {
+        return Err(ContractError::InvalidEnrollment { msg: "term_of_payments and funds must be sent with 'enroll'".to_string() });
+    }
+    Achievement::new(&metrics)?;
+
+    program(deps.storage).save(&Program { payer: info.sender, metrics })?;
+    Ok(Response::default())
+}
+
+fn instantiate_reporters(
+    deps: DepsMut,
+    metrics: &[MetricSpec],
+    reporters: Vec<Reporter>,
+    quorum: u32,
+) -> Result<(), ContractError> {
+    if quorum == 0 {
+        return Err(ContractError::InvalidReporters { msg: "quorum must be greater than 0".to_string() });
+    }
+
+    let mut validated: Vec<Reporter> = vec![];
+    for reporter in reporters {
+        let address = deps.api.addr_validate(&reporter.address)?.to_string();
+        if validated.iter().any(|r| r.address == address) {
+            return Err(ContractError::InvalidReporters { msg: format!("duplicated reporter {}", address) });
+        }
+        if let Some(metric) = reporter.metrics.iter().find(|m| !metrics.iter().any(|spec| &spec.name == *m)) {
+            return Err(ContractError::InvalidMetric { name: metric.to_string(), msg: "undeclared metric".to_string() });
+        }
+        validated.push(Reporter { address, metrics: reporter.metrics });
+    }
+
+    // every reported metric must be able to reach the quorum
+    for spec in metrics {
+        let n = validated.iter().filter(|r| r.metrics.contains(&spec.name)).count();
+        if n > 0 && n < quorum as usize {
+            return Err(ContractError::InvalidReporters { msg: format!("{} reporters of {} are less than the quorum", n, spec.name) });
+        }
+    }
+
+    reporter_set(deps.storage).save(&ReporterSet { reporters: validated, quorum })?;
+    Ok(())
+}
+
+fn default_metrics() -> Vec<MetricSpec> {
+    vec![
+        MetricSpec { name: "treatments".to_string(), kind: MetricKind::Counter },
+        MetricSpec { name: "insurance_claim".to_string(), kind: MetricKind::Boolean },
+    ]
+}
+
+/// Checks that the terms can be paid by `funds` before `end_time`.
+fn validate_term_of_payments(
+    achievement: &Achievement,
+    term_of_payments: &[TermOfPayment],
+    funds: &[Coin],
+    start_time: Timestamp,
+    end_time: Option<Timestamp>,
+) -> Result<(), ContractError> {
+    if term_of_payments.is_empty() {
+        return Err(ContractError::EmptyTermOfPayments {});
+    }
+
+    for (i, term_of_payment) in term_of_payments.iter().enumerate() {
+        if term_of_payments[..i].iter().any(|t| t.id == term_of_payment.id) {
+            return Err(ContractError::DuplicatedTermOfPayment { id: term_of_payment.id.to_string() });
+        }
+        validate_term_of_payment(achievement, term_of_payment, start_time, end_time)?;
+    }
+
+    check_funds(term_of_payments, funds)?;
+    if let Some(coin) = funds.iter().find(|c| !term_of_payments.iter().any(|t| t.amount.denom == c.denom)) {
+        return Err(ContractError::UnexpectedDenom { denom: coin.denom.to_string() });
+    }
+    Ok(())
+}
+
+/// Checks a new term, which must not be paid and must be achievable before `end_time`.
+fn validate_term_of_payment(
+    achievement: &Achievement,
+    term_of_payment: &TermOfPayment,
+    start_time: Timestamp,
+    end_time: Option<Timestamp>,
+) -> Result<(), ContractError> {
+    let id = term_of_payment.id.to_string();
+    if term_of_payment.is_payment {
+        return Err(ContractError::PaidTermOfPayment { id });
+    }
+    if term_of_payment.amount.amount.is_zero() {
+        return Err(ContractError::ZeroAmount { id });
+    }
+    achievement.validate_condition(&term_of_payment.condition, start_time)?;
+    if let Some(end_time) = end_time {
+        let reachable = term_of_payment.condition.required_seconds()
+            .and_then(|seconds| Duration::Seconds(seconds).after(start_time))
+            .map_or(false, |time| time <= end_time);
+        if !reachable {
+            return Err(ContractError::UnreachableTermOfPayment { id, end_time });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `funds` cover the sum of the unpaid terms per denom.
+fn check_funds(term_of_payments: &[TermOfPayment], funds: &[Coin]) -> Result<(), ContractError> {
+    let mut required: Vec<Coin> = vec![];
+    for term_of_payment in term_of_payments.iter().filter(|t| !t.is_payment) {
+        match required.iter_mut().find(|c| c.denom == term_of_payment.amount.denom) {
+            Some(coin) => coin.amount = coin.amount.checked_add(term_of_payment.amount.amount).map_err(StdError::from)?,
+            None => required.push(term_of_payment.amount.clone()),
+        }
+    }
+
+    for coin in required {
+        let funded = funds.iter()
+            .find(|c| c.denom == coin.denom)
+            .map_or_else(|| Coin::new(0, coin.denom.to_string()), |c| c.clone());
+        if funded.amount < coin.amount {
+            return Err(ContractError::InsufficientFunds { required: coin, funds: funded });
+        }
+    }
+    Ok(())
+}
+
 pub fn execute(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    let mut state = config_read(deps.storage).load()?;
     match msg {
-        ExecuteMsg::Approve {} => try_approve(deps, &env, state, info),
-        ExecuteMsg::Append { treatments } => try_append(deps, &env, &mut state, info, treatments),
-        ExecuteMsg::Update { treatments, insurance_claim } => try_update(deps, &env, &mut state, info, treatments, insurance_claim),
-        ExecuteMsg::Refund {} => try_refund(deps, &env, state),
+        ExecuteMsg::Approve {} => {
+            let state = load_state(deps.as_ref(), Some(info.sender.to_string()))?;
+            try_approve(deps, &env, state, info)
+        }
+        ExecuteMsg::Append { metric, n, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_append(deps, &env, &mut state, info, metric, n)
+        }
+        ExecuteMsg::Update { metrics, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_update(deps, &env, &mut state, info, metrics)
+        }
+        ExecuteMsg::Refund { joiner } => {
+            let state = load_state(deps.as_ref(), joiner)?;
+            try_refund(deps, &env, state)
+        }
+        ExecuteMsg::Report { metric, evidence_hash, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_report(deps, &env, &mut state, info, metric, evidence_hash)
+        }
+        ExecuteMsg::ClaimAchievement { metric, value, evidence_hash, nonce, signature } => {
+            let state = load_state(deps.as_ref(), Some(info.sender.to_string()))?;
+            let payload = ClaimPayload {
+                contract: env.contract.address.to_string(),
+                joiner: info.sender.to_string(),
+                metric,
+                value,
+                evidence_hash,
+                nonce,
+            };
+            try_claim_achievement(deps, &env, state, payload, signature)
+        }
+        ExecuteMsg::Dispute { id, reason, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_dispute(deps, &env, &mut state, info, id, reason)
+        }
+        ExecuteMsg::ResolveDispute { id, approve, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_resolve_dispute(deps, &env, &mut state, info, id, approve)
+        }
+        ExecuteMsg::ClaimPayout { id } => {
+            let mut state = load_state(deps.as_ref(), Some(info.sender.to_string()))?;
+            try_claim_payout(deps, &env, &mut state, info, id)
+        }
+        ExecuteMsg::RefundExcess { joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_refund_excess(deps, &env, &mut state, info)
+        }
+        ExecuteMsg::Enroll { joiner, term_of_payments, end_time } => try_enroll(deps, &env, info, joiner, term_of_payments, end_time),
+        ExecuteMsg::TopUp { joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_top_up(deps, &env, &mut state, info)
+        }
+        ExecuteMsg::ProposeAmendment { amendment, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_propose_amendment(deps, &env, &mut state, info, amendment)
+        }
+        ExecuteMsg::AcceptAmendment { id, joiner } => {
+            let mut state = load_state(deps.as_ref(), joiner)?;
+            try_accept_amendment(deps, &env, &mut state, info, id)
+        }
+    }
+}
+
+/// Loads the state of the contract, or the enrollment of `joiner` if the contract is a program.
+fn load_state(deps: Deps, joiner: Option<String>) -> Result<State, ContractError> {
+    if let Some(state) = config_read(deps.storage).may_load()? {
+        return Ok(state);
+    }
+
+    let joiner = joiner.ok_or_else(|| ContractError::InvalidEnrollment { msg: "joiner is required".to_string() })?;
+    let joiner = deps.api.addr_validate(&joiner)?;
+    enrollments_read(deps.storage)
+        .may_load(joiner.as_bytes())?
+        .ok_or_else(|| ContractError::NotEnrolled { joiner: joiner.to_string() })
+}
+
+fn save_state(storage: &mut dyn Storage, state: &State) -> StdResult<()> {
+    if program_read(storage).may_load()?.is_some() {
+        return enrollments(storage).save(state.joiner.as_bytes(), state);
+    }
+    config(storage).save(state)
+}
+
+/// Saves `state`, or removes the enrollment of an expired program once it holds no unpaid or reserved funds.
+fn save_or_close_enrollment(storage: &mut dyn Storage, env: &Env, state: &State) -> StdResult<()> {
+    if program_read(storage).may_load()?.is_some()
+        && state.is_expired(env)
+        && state.unpaid_amounts().is_empty()
+        && state.reserved_amounts()?.is_empty() {
+        enrollments(storage).remove(state.joiner.as_bytes());
+        return Ok(());
+    }
+    save_state(storage, state)
+}
+
+fn try_enroll(
+    deps: DepsMut,
+    env: &Env,
+    info: MessageInfo,
+    joiner: String,
+    term_of_payments: Vec<TermOfPayment>,
+    end_time: Option<Timestamp>,
+) -> Result<Response, ContractError> {
+    let program = program_read(deps.storage).may_load()?
+        .ok_or_else(|| ContractError::InvalidEnrollment { msg: "Not a program contract".to_string() })?;
+    if info.sender != program.payer {
+        return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'payer'".to_string() });
+    }
+
+    let joiner = deps.api.addr_validate(&joiner)?;
+    if enrollments_read(deps.storage).may_load(joiner.as_bytes())?.is_some() {
+        return Err(ContractError::AlreadyEnrolled { joiner: joiner.to_string() });
+    }
+
+    let achievement = Achievement::new(&program.metrics)?;
+    validate_term_of_payments(&achievement, &term_of_payments, &info.funds, env.block.time, end_time)?;
+    let state = State {
+        payer: info.sender,
+        joiner,
+        total_amount: info.funds,
+        term_of_payments,
+        achievement,
+        start_time: env.block.time,
+        end_time,
+        pending_amendment: None,
+        amendments: vec![],
+        reports: vec![],
+        accepted_reports: vec![],
+        claim_nonce: 0,
+        payouts: vec![],
+    };
+
+    if state.is_expired(&env) {
+        return Err(ContractError::Expired {
+            end_time,
+        });
     }
+
+    enrollments(deps.storage).save(state.joiner.as_bytes(), &state)?;
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        data: None,
+        attributes: vec![attr("action", "enroll"), attr("joiner", state.joiner)],
+    })
 }
 
 fn try_append(
@@ -53,21 +362,23 @@ fn try_append(
     env: &Env,
     state: &mut State,
     info: MessageInfo,
-    treatments: Option<u64>,
+    metric: String,
+    n: u64,
 ) -> Result<Response, ContractError> {
     if info.sender != state.payer {
         return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'payer'".to_string() });
     }
+    check_no_reporters(deps.storage, &metric)?;
 
     if state.is_expired(&env) {
         return Err(ContractError::Expired {
-            end_time: state.end_time_millis,
+            end_time: state.end_time,
         });
     }
 
-    state.append_treatments_in_achievement(treatments);
+    state.achievement.append(&metric, n)?;
 
-    config(deps.storage).save(state)?;
+    save_state(deps.storage, state)?;
     Ok(Response::default())
 }
 
@@ -76,25 +387,144 @@ fn try_update(
     env: &Env,
     state: &mut State,
     info: MessageInfo,
-    treatments: Option<u64>,
-    insurance_claim: Option<bool>,
+    metrics: Vec<Metric>,
 ) -> Result<Response, ContractError> {
     if info.sender != state.payer {
         return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'payer'".to_string() });
     }
+    for metric in metrics.iter() {
+        check_no_reporters(deps.storage, &metric.name)?;
+    }
 
     if state.is_expired(&env) {
         return Err(ContractError::Expired {
-            end_time: state.end_time_millis,
+            end_time: state.end_time,
         });
     }
 
-    state.update_treatments_in_achievement(treatments, insurance_claim);
+    state.achievement.update(metrics)?;
 
-    config(deps.storage).save(&state)?;
+    save_state(deps.storage, state)?;
     Ok(Response::default())
 }
 
+fn try_report(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+    metric: Metric,
+    evidence_hash: String,
+) -> Result<Response, ContractError> {
+    let reporter_set = reporter_set_read(deps.storage).may_load()?
+        .ok_or_else(|| ContractError::Unauthorized { msg: "No registered reporters".to_string() })?;
+    if !reporter_set.is_reporter(&info.sender, &metric.name) {
+        return Err(ContractError::Unauthorized { msg: format!("Not a reporter of '{}'", metric.name) });
+    }
+
+    if state.is_expired(env) {
+        return Err(ContractError::Expired {
+            end_time: state.end_time,
+        });
+    }
+
+    if evidence_hash.is_empty() {
+        return Err(ContractError::InvalidReport { msg: "evidence_hash is required".to_string() });
+    }
+    state.achievement.expect_kind(&metric.name, &[metric.value.kind()])?;
+
+    // only the latest report of a reporter counts
+    state.reports.retain(|r| r.reporter != info.sender || r.metric.name != metric.name);
+    state.reports.push(Report {
+        reporter: info.sender,
+        metric: metric.clone(),
+        evidence_hash,
+        reported_at: env.block.time,
+    });
+
+    let agreed = state.reports.iter().filter(|r| r.metric == metric).count();
+    let accepted = agreed >= reporter_set.quorum as usize;
+    let mut attributes = vec![attr("action", "report"), attr("metric", &metric.name), attr("accepted", accepted)];
+    if accepted {
+        // the agreeing reports are kept as accepted, the other reports of the metric are dropped
+        let agreeing = state.reports.iter().filter(|r| r.metric == metric).cloned().collect::<Vec<Report>>();
+        let evidence_hashes = agreeing.iter()
+            .map(|r| r.evidence_hash.as_str())
+            .collect::<Vec<&str>>()
+            .join(",");
+        attributes.push(attr("evidence_hashes", evidence_hashes));
+        state.reports.retain(|r| r.metric.name != metric.name);
+        state.accepted_reports.extend(agreeing);
+        state.achievement.update(vec![metric])?;
+    }
+
+    save_state(deps.storage, state)?;
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        data: None,
+        attributes,
+    })
+}
+
+fn try_claim_achievement(
+    deps: DepsMut,
+    env: &Env,
+    mut state: State,
+    payload: ClaimPayload,
+    signature: Binary,
+) -> Result<Response, ContractError> {
+    let provider = data_provider_read(deps.storage).may_load()?
+        .ok_or_else(|| ContractError::Unauthorized { msg: "No data provider".to_string() })?;
+    // a claim would bypass the quorum of the reporters
+    check_no_reporters(deps.storage, &payload.metric)?;
+
+    if state.is_expired(env) {
+        return Err(ContractError::Expired {
+            end_time: state.end_time,
+        });
+    }
+
+    if payload.evidence_hash.is_empty() {
+        return Err(ContractError::InvalidReport { msg: "evidence_hash is required".to_string() });
+    }
+    if payload.nonce <= state.claim_nonce {
+        return Err(ContractError::InvalidNonce { nonce: payload.nonce, last: state.claim_nonce });
+    }
+    verify_claim(deps.as_ref(), &provider, &payload, &signature)?;
+
+    state.achievement.update(vec![Metric { name: payload.metric.clone(), value: payload.value }])?;
+    state.claim_nonce = payload.nonce;
+
+    save_state(deps.storage, &state)?;
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        data: None,
+        attributes: vec![attr("action", "claim_achievement"), attr("metric", payload.metric), attr("nonce", payload.nonce)],
+    })
+}
+
+fn verify_claim(deps: Deps, provider: &DataProvider, payload: &ClaimPayload, signature: &Binary) -> Result<(), ContractError> {
+    let message = to_vec(payload)?;
+    let verified = match provider.scheme {
+        SignatureScheme::Secp256k1 => deps.api.secp256k1_verify(&Sha256::digest(&message), signature, &provider.public_key),
+        SignatureScheme::Ed25519 => deps.api.ed25519_verify(&message, signature, &provider.public_key),
+    };
+    match verified {
+        Ok(true) => Ok(()),
+        _ => Err(ContractError::InvalidSignature {}),
+    }
+}
+
+/// A metric with registered reporters is only set by their reports.
+fn check_no_reporters(storage: &dyn Storage, metric: &str) -> Result<(), ContractError> {
+    if reporter_set_read(storage).may_load()?.map_or(false, |reporter_set| reporter_set.is_reported(metric)) {
+        return Err(ContractError::Unauthorized { msg: format!("'{}' is reported by the registered reporters", metric) });
+    }
+    Ok(())
+}
+
 fn try_approve(
     deps: DepsMut,
     env: &Env,
@@ -107,7 +537,7 @@ fn try_approve(
 
     if state.is_expired(&env) {
         return Err(ContractError::Expired {
-            end_time: state.end_time_millis,
+            end_time: state.end_time,
         });
     }
 
@@ -117,131 +547,530 @@ fn try_approve(
         return Err(ContractError::NotAchievementContract {});
     }
 
-    config(deps.storage).save(&state)?;
+    // with a challenge period, the reward is paid by 'claim_payout' later
+    if challenge_window_read(deps.storage).may_load()?.is_some() {
+        let id = state.open_payout(amount, env);
+        save_state(deps.storage, &state)?;
+        return Ok(Response {
+            submessages: vec![],
+            messages: vec![],
+            data: None,
+            attributes: vec![attr("action", "approve"), attr("payout", id)],
+        });
+    }
+
+    save_state(deps.storage, &state)?;
 
     Ok(send_tokens(state.joiner, amount, "approve"))
 }
 
-fn try_refund(
+fn try_dispute(
     deps: DepsMut,
     env: &Env,
-    state: State,
+    state: &mut State,
+    info: MessageInfo,
+    id: u64,
+    reason: String,
 ) -> Result<Response, ContractError> {
-    // anyone can try to refund, as long as the contract is expired
-    if !state.is_expired(&env) {
-        return Err(ContractError::NotExpired {});
+    if info.sender != state.payer {
+        return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'payer'".to_string() });
     }
 
-    let balance = deps.querier.query_all_balances(&env.contract.address)?;
-    Ok(send_tokens(state.payer, balance, "refund"))
-}
+    if reason.is_empty() {
+        return Err(ContractError::InvalidPayout { id, msg: "reason is required".to_string() });
+    }
 
-fn send_tokens(to_address: Addr, amount: Vec<Coin>, action: &str) -> Response {
-    let attributes = vec![attr("action", action), attr("to", to_address.clone())];
+    let window = load_challenge_window(deps.storage)?;
+    let payout = pending_payout(state, id)?;
+    // a period overflowing after the approval never ends
+    if window.period.after(payout.approved_at).map_or(false, |end| env.block.time >= end) {
+        return Err(ContractError::ChallengePeriodEnded { id });
+    }
+    payout.status = PayoutStatus::Disputed;
+    payout.dispute_reason = Some(reason);
 
-    Response {
+    save_state(deps.storage, state)?;
+    Ok(Response {
         submessages: vec![],
-        messages: vec![CosmosMsg::Bank(BankMsg::Send {
-            to_address: to_address.into(),
-            amount,
-        })],
+        messages: vec![],
         data: None,
-        attributes,
-    }
+        attributes: vec![attr("action", "dispute"), attr("payout", id), attr("arbiter", window.arbiter)],
+    })
 }
 
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::TermOfPayments {} => to_binary(&query_term_of_payments(deps)?),
-        QueryMsg::Achievement {} => to_binary(&query_achievement(deps)?),
-        QueryMsg::Joiner {} => to_binary(&query_joiner(deps)?),
+fn try_resolve_dispute(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+    id: u64,
+    approve: bool,
+) -> Result<Response, ContractError> {
+    let window = load_challenge_window(deps.storage)?;
+    if info.sender != window.arbiter {
+        return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'arbiter'".to_string() });
+    }
+
+    let payout = state.payouts.iter_mut()
+        .find(|p| p.id == id)
+        .ok_or_else(|| ContractError::InvalidPayout { id, msg: "not found".to_string() })?;
+    if payout.status != PayoutStatus::Disputed {
+        return Err(ContractError::InvalidPayout { id, msg: format!("{:?} payout", payout.status) });
     }
+
+    // a rejected payout is refunded to the payer, and its terms are not paid again
+    let (to_address, action) = if approve {
+        payout.status = PayoutStatus::Paid;
+        (state.joiner.clone(), "resolve_dispute_approve")
+    } else {
+        payout.status = PayoutStatus::Rejected;
+        (state.payer.clone(), "resolve_dispute_reject")
+    };
+    let amount = payout.amount.clone();
+
+    save_or_close_enrollment(deps.storage, env, state)?;
+    Ok(send_tokens(to_address, amount, action))
 }
 
-fn query_term_of_payments(deps: Deps) -> StdResult<TermOfPaymentsResponse> {
-    let state = config_read(deps.storage).load()?;
-    let term_of_payments = state.term_of_payments;
-    Ok(TermOfPaymentsResponse { term_of_payments })
+fn try_claim_payout(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    if info.sender != state.joiner {
+        return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'joiner'".to_string() });
+    }
+
+    let window = load_challenge_window(deps.storage)?;
+    let payout = pending_payout(state, id)?;
+    if window.period.after(payout.approved_at).map_or(true, |end| env.block.time < end) {
+        return Err(ContractError::ChallengePeriodNotEnded { id });
+    }
+    payout.status = PayoutStatus::Paid;
+    let amount = payout.amount.clone();
+
+    save_or_close_enrollment(deps.storage, env, state)?;
+    Ok(send_tokens(state.joiner.clone(), amount, "claim_payout"))
 }
 
-fn query_achievement(deps: Deps) -> StdResult<AchievementResponse> {
-    let state = config_read(deps.storage).load()?;
-    let achievement = state.achievement;
-    Ok(AchievementResponse { achievement })
+fn load_challenge_window(storage: &dyn Storage) -> Result<ChallengeWindow, ContractError> {
+    challenge_window_read(storage).may_load()?
+        .ok_or(ContractError::NoChallengePeriod {})
 }
 
-fn query_joiner(deps: Deps) -> StdResult<JoinerResponse> {
-    let state = config_read(deps.storage).load()?;
-    let joiner = state.joiner;
-    Ok(JoinerResponse { joiner })
+fn pending_payout(state: &mut State, id: u64) -> Result<&mut Payout, ContractError> {
+    let payout = state.payouts.iter_mut()
+        .find(|p| p.id == id)
+        .ok_or_else(|| ContractError::InvalidPayout { id, msg: "not found".to_string() })?;
+    if payout.status != PayoutStatus::Pending {
+        return Err(ContractError::InvalidPayout { id, msg: format!("{:?} payout", payout.status) });
+    }
+    Ok(payout)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+fn try_top_up(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    if info.sender != state.payer {
+        return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'payer'".to_string() });
+    }
 
-    use cosmwasm_std::{Addr, BankMsg, coin, coins, CosmosMsg, Timestamp};
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    if state.is_expired(env) {
+        return Err(ContractError::Expired {
+            end_time: state.end_time,
+        });
+    }
 
-    use crate::contract::{execute, instantiate, try_append, try_approve, try_refund, try_update};
-    use crate::error::ContractError;
-    use crate::error::ContractError::Unauthorized;
-    use crate::msg::{ContractContent, ExecuteMsg, InstantiateMsg, TermOfPayment};
-    use crate::state::config_read;
+    if info.funds.iter().all(|c| c.amount.is_zero()) {
+        return Err(ContractError::NoFunds {});
+    }
 
-    fn init_msg(end_time_millis: u64, term_of_payments: Vec<TermOfPayment>) -> InstantiateMsg {
-        InstantiateMsg {
-            joiner: String::from("joiner"),
-            term_of_payments,
-            end_time_millis: Some(end_time_millis),
+    for coin in info.funds.into_iter().filter(|c| !c.amount.is_zero()) {
+        match state.total_amount.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(amount) => amount.amount = amount.amount.checked_add(coin.amount).map_err(StdError::from)?,
+            None => state.total_amount.push(coin),
         }
     }
 
-    #[test]
-    fn proper_initialization() {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-
-        let mut deps = mock_dependencies(&[]);
-        let mut env = mock_env();
-        env.block.height = 876;
-        env.block.time = Timestamp::from_seconds(now.as_secs());
-        let info = mock_info("payer", &coins(100000000000, "umed"));
-        let term_of_payments = vec![
-            TermOfPayment {
-                id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
-                amount: coin(100000000000, "umed"),
-                is_payment: false,
-            }
-        ];
+    save_state(deps.storage, state)?;
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        data: None,
+        attributes: vec![attr("action", "top_up"), attr("joiner", state.joiner.clone())],
+    })
+}
 
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+fn try_propose_amendment(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+    amendment: Amendment,
+) -> Result<Response, ContractError> {
+    if info.sender != state.payer && info.sender != state.joiner {
+        return Err(ContractError::Unauthorized { msg: "Not 'payer' or 'joiner'".to_string() });
+    }
 
-        let result = instantiate(deps.as_mut(), env, info.clone(), msg.clone());
-        assert_eq!(true, result.is_ok());
-        let response = result.unwrap();
-        assert_eq!(0, response.messages.len());
+    if state.is_expired(env) {
+        return Err(ContractError::Expired {
+            end_time: state.end_time,
+        });
+    }
 
-        let state = config_read(&mut deps.storage).load().unwrap();
+    // the amendment must be applicable as proposed
+    apply_amendment(&mut state.clone(), &amendment)?;
+
+    let id = state.pending_amendment.as_ref()
+        .or_else(|| state.amendments.last())
+        .map_or(1, |a| a.id + 1);
+    state.pending_amendment = Some(AmendmentRecord {
+        id,
+        proposer: info.sender,
+        amendment,
+        proposed_at: env.block.time,
+        accepted_at: None,
+    });
+
+    save_state(deps.storage, state)?;
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        data: None,
+        attributes: vec![attr("action", "propose_amendment"), attr("id", id)],
+    })
+}
+
+fn try_accept_amendment(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    if info.sender != state.payer && info.sender != state.joiner {
+        return Err(ContractError::Unauthorized { msg: "Not 'payer' or 'joiner'".to_string() });
+    }
+
+    if state.is_expired(env) {
+        return Err(ContractError::Expired {
+            end_time: state.end_time,
+        });
+    }
+
+    let mut record = match state.pending_amendment.take() {
+        Some(record) if record.id == id => record,
+        _ => return Err(ContractError::InvalidAmendment { msg: format!("No pending amendment {}", id) }),
+    };
+    if record.proposer == info.sender {
+        return Err(ContractError::Unauthorized { msg: "The proposer cannot accept the amendment".to_string() });
+    }
+
+    apply_amendment(state, &record.amendment)?;
+    record.accepted_at = Some(env.block.time);
+    state.amendments.push(record);
+
+    save_state(deps.storage, state)?;
+    Ok(Response {
+        submessages: vec![],
+        messages: vec![],
+        data: None,
+        attributes: vec![attr("action", "accept_amendment"), attr("id", id)],
+    })
+}
+
+/// Applies `amendment` to the unpaid terms, which must stay covered by the unpaid funds.
+fn apply_amendment(state: &mut State, amendment: &Amendment) -> Result<(), ContractError> {
+    if let Some(end_time) = amendment.end_time {
+        match state.end_time {
+            Some(current) if end_time > current => state.end_time = Some(end_time),
+            _ => return Err(ContractError::InvalidAmendment { msg: "end_time can only be extended".to_string() }),
+        }
+    }
+
+    for id in &amendment.remove {
+        let i = unpaid_term_of_payment(&state.term_of_payments, id)?;
+        state.term_of_payments.remove(i);
+    }
+    for reprice in &amendment.reprice {
+        let i = unpaid_term_of_payment(&state.term_of_payments, &reprice.id)?;
+        if reprice.amount.amount.is_zero() {
+            return Err(ContractError::ZeroAmount { id: reprice.id.to_string() });
+        }
+        state.term_of_payments[i].amount = reprice.amount.clone();
+    }
+    for term_of_payment in &amendment.add {
+        if state.term_of_payments.iter().any(|t| t.id == term_of_payment.id) {
+            return Err(ContractError::DuplicatedTermOfPayment { id: term_of_payment.id.to_string() });
+        }
+        validate_term_of_payment(&state.achievement, term_of_payment, state.start_time, state.end_time)?;
+        state.term_of_payments.push(term_of_payment.clone());
+    }
+
+    check_funds(&state.term_of_payments, &state.unpaid_amounts())
+}
+
+/// Index of the unpaid term `id`
+fn unpaid_term_of_payment(term_of_payments: &[TermOfPayment], id: &str) -> Result<usize, ContractError> {
+    let i = term_of_payments.iter()
+        .position(|t| t.id == id)
+        .ok_or_else(|| ContractError::UnknownTermOfPayment { id: id.to_string() })?;
+    if term_of_payments[i].is_payment {
+        return Err(ContractError::PaidTermOfPayment { id: id.to_string() });
+    }
+    Ok(i)
+}
+
+fn try_refund(
+    deps: DepsMut,
+    env: &Env,
+    mut state: State,
+) -> Result<Response, ContractError> {
+    // anyone can try to refund, as long as the contract is expired
+    if !state.is_expired(&env) {
+        return Err(ContractError::NotExpired {});
+    }
+
+    // the terms achieved before end_time are settled to the joiner first
+    let mut end_env = env.clone();
+    if let Some(end_time) = state.end_time {
+        end_env.block.time = end_time;
+    }
+    let mut earned = state.update_contract_achievement_and_get_amounts(&end_env);
+    if !earned.is_empty() && challenge_window_read(deps.storage).may_load()?.is_some() {
+        state.open_payout(earned, env);
+        earned = vec![];
+    }
+    let reserved = state.reserved_amounts()?;
+
+    // a program refunds only the unpaid funds of the enrollment, and keeps it until its payouts are closed
+    let amount = if program_read(deps.storage).may_load()?.is_some() {
+        let amount = state.unpaid_amounts();
+        state.total_amount = subtract_amounts(state.total_amount.clone(), &amount);
+        save_or_close_enrollment(deps.storage, env, &state)?;
+        amount
+    } else {
+        config(deps.storage).save(&state)?;
+        let balance = deps.querier.query_all_balances(&env.contract.address)?;
+        subtract_amounts(subtract_amounts(balance, &earned), &reserved)
+    };
+
+    let mut response = send_tokens(state.payer, amount, "refund");
+    if !earned.is_empty() {
+        response.messages.insert(0, CosmosMsg::Bank(BankMsg::Send {
+            to_address: state.joiner.to_string(),
+            amount: earned,
+        }));
+        response.attributes.push(attr("settled_to", state.joiner));
+    }
+    Ok(response)
+}
+
+fn try_refund_excess(
+    deps: DepsMut,
+    env: &Env,
+    state: &mut State,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    if info.sender != state.payer {
+        return Err(ContractError::Unauthorized { msg: "Not same 'sender' and 'payer'".to_string() });
+    }
+
+    if state.is_expired(env) {
+        return Err(ContractError::Expired {
+            end_time: state.end_time,
+        });
+    }
+
+    let amount = state.excess_amounts()?;
+    if amount.is_empty() {
+        return Err(ContractError::NoFunds {});
+    }
+    state.total_amount = subtract_amounts(state.total_amount.clone(), &amount);
+
+    save_state(deps.storage, state)?;
+    Ok(send_tokens(state.payer.clone(), amount, "refund_excess"))
+}
+
+fn send_tokens(to_address: Addr, amount: Vec<Coin>, action: &str) -> Response {
+    let attributes = vec![attr("action", action), attr("to", to_address.clone())];
+    let messages = if amount.is_empty() {
+        vec![]
+    } else {
+        vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: to_address.into(),
+            amount,
+        })]
+    };
+
+    Response {
+        submessages: vec![],
+        messages,
+        data: None,
+        attributes,
+    }
+}
+
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::TermOfPayments { joiner } => to_binary(&query_term_of_payments(deps, joiner)?),
+        QueryMsg::Achievement { joiner } => to_binary(&query_achievement(deps, joiner)?),
+        QueryMsg::Joiner {} => to_binary(&query_joiner(deps)?),
+        QueryMsg::Enrollments { start_after, limit } => to_binary(&query_enrollments(deps, start_after, limit)?),
+        QueryMsg::Amendments { joiner } => to_binary(&query_amendments(deps, joiner)?),
+        QueryMsg::Reporters {} => to_binary(&query_reporters(deps)?),
+        QueryMsg::DataProvider {} => to_binary(&query_data_provider(deps)?),
+        QueryMsg::Challenge {} => to_binary(&query_challenge(deps)?),
+        QueryMsg::Payouts { joiner } => to_binary(&query_payouts(deps, joiner)?),
+        QueryMsg::Reports { joiner } => to_binary(&query_reports(deps, joiner)?),
+    }
+}
+
+fn query_term_of_payments(deps: Deps, joiner: Option<String>) -> StdResult<TermOfPaymentsResponse> {
+    let state = load_state(deps, joiner).map_err(|e| StdError::generic_err(e.to_string()))?;
+    let term_of_payments = state.term_of_payments;
+    Ok(TermOfPaymentsResponse { term_of_payments })
+}
+
+fn query_achievement(deps: Deps, joiner: Option<String>) -> StdResult<AchievementResponse> {
+    let state = load_state(deps, joiner).map_err(|e| StdError::generic_err(e.to_string()))?;
+    let achievement = state.achievement;
+    Ok(AchievementResponse { achievement })
+}
+
+fn query_amendments(deps: Deps, joiner: Option<String>) -> StdResult<AmendmentsResponse> {
+    let state = load_state(deps, joiner).map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok(AmendmentsResponse { pending: state.pending_amendment, amendments: state.amendments })
+}
+
+fn query_reporters(deps: Deps) -> StdResult<ReportersResponse> {
+    let reporter_set = reporter_set_read(deps.storage).load()?;
+    Ok(ReportersResponse { reporters: reporter_set.reporters, quorum: reporter_set.quorum })
+}
+
+fn query_data_provider(deps: Deps) -> StdResult<DataProviderResponse> {
+    let data_provider = data_provider_read(deps.storage).load()?;
+    Ok(DataProviderResponse { data_provider })
+}
+
+fn query_challenge(deps: Deps) -> StdResult<ChallengeResponse> {
+    let window = challenge_window_read(deps.storage).load()?;
+    Ok(ChallengeResponse { period: window.period, arbiter: window.arbiter })
+}
+
+fn query_payouts(deps: Deps, joiner: Option<String>) -> StdResult<PayoutsResponse> {
+    let state = load_state(deps, joiner).map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok(PayoutsResponse { payouts: state.payouts })
+}
+
+fn query_reports(deps: Deps, joiner: Option<String>) -> StdResult<ReportsResponse> {
+    let state = load_state(deps, joiner).map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok(ReportsResponse { reports: state.reports, accepted_reports: state.accepted_reports })
+}
+
+fn query_joiner(deps: Deps) -> StdResult<JoinerResponse> {
+    if program_read(deps.storage).may_load()?.is_some() {
+        return Err(StdError::generic_err("Program contract has no single joiner, query 'enrollments'"));
+    }
+    let state = config_read(deps.storage).load()?;
+    let joiner = state.joiner;
+    Ok(JoinerResponse { joiner })
+}
+
+fn query_enrollments(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<EnrollmentsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|joiner| {
+        let mut start = joiner.into_bytes();
+        start.push(0);
+        start
+    });
+
+    let enrollments = enrollments_read(deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, state)| state))
+        .collect::<StdResult<Vec<State>>>()?;
+    Ok(EnrollmentsResponse { enrollments })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use cosmwasm_std::{Addr, BankMsg, Binary, Coin, coin, coins, CosmosMsg, StdError, Timestamp, to_vec};
+    use k256::ecdsa::signature::Signer;
+    use cosmwasm_std::testing::{MOCK_CONTRACT_ADDR, mock_dependencies, mock_env, mock_info};
+
+    use crate::contract::{execute, instantiate, query_amendments, query_enrollments, query_joiner, query_payouts, query_reports, try_append, try_approve, try_refund, try_update};
+    use crate::error::ContractError;
+    use crate::error::ContractError::Unauthorized;
+    use crate::msg::{Amendment, Challenge, ClaimPayload, Condition, DataProvider, Duration, ExecuteMsg, InstantiateMsg, Metric, MetricKind, MetricSpec, MetricValue, Reporter, Reprice, SignatureScheme, TermOfPayment};
+    use crate::state::{Achievement, config_read, PayoutStatus, Report};
+
+    fn treatments_and_insurance_claim(treatments: u64) -> Condition {
+        Condition::All(vec![
+            Condition::MinCount { metric: "treatments".to_string(), n: treatments },
+            Condition::Flag { name: "insurance_claim".to_string() },
+        ])
+    }
+
+    fn init_msg(end_time: Timestamp, term_of_payments: Vec<TermOfPayment>) -> InstantiateMsg {
+        InstantiateMsg {
+            joiner: Some(String::from("joiner")),
+            term_of_payments,
+            end_time: Some(end_time),
+            metrics: None,
+            reporters: None,
+            quorum: None,
+            data_provider: None,
+            challenge: None,
+        }
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+        let mut deps = mock_dependencies(&[]);
+        let mut env = mock_env();
+        env.block.height = 876;
+        env.block.time = Timestamp::from_seconds(now.as_secs());
+        let info = mock_info("payer", &coins(100000000000, "umed"));
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: treatments_and_insurance_claim(100),
+                amount: coin(100000000000, "umed"),
+                is_payment: false,
+            }
+        ];
+
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
+
+        let result = instantiate(deps.as_mut(), env, info.clone(), msg.clone());
+        assert_eq!(true, result.is_ok());
+        let response = result.unwrap();
+        assert_eq!(0, response.messages.len());
+
+        let state = config_read(&mut deps.storage).load().unwrap();
 
         assert_eq!(&info.sender, &state.payer);
-        assert_eq!(&msg.joiner, &state.joiner);
+        assert_eq!(msg.joiner.as_deref(), Some(state.joiner.as_str()));
         assert_eq!(&info.funds, &state.total_amount);
         assert_eq!(1, state.term_of_payments.len());
         assert_eq!(&msg.term_of_payments[0].id, &state.term_of_payments[0].id);
-        assert_eq!(&msg.term_of_payments[0].contract_content.treatments, &state.term_of_payments[0].contract_content.treatments);
-        assert_eq!(&msg.term_of_payments[0].contract_content.insurance_claim, &state.term_of_payments[0].contract_content.insurance_claim);
-        assert_eq!(&msg.term_of_payments[0].contract_content.period_days, &state.term_of_payments[0].contract_content.period_days);
+        assert_eq!(&msg.term_of_payments[0].condition, &state.term_of_payments[0].condition);
         assert_eq!(&msg.term_of_payments[0].amount, &state.term_of_payments[0].amount);
         assert_eq!(&msg.term_of_payments[0].is_payment, &state.term_of_payments[0].is_payment);
-        assert_eq!(0, state.achievement.treatments);
-        assert_eq!(false, state.achievement.insurance_claim);
-        assert_eq!(msg.end_time_millis, state.end_time_millis);
-        assert_eq!(Some(Timestamp::from_seconds(now.as_secs()).nanos() / 1000), state.start_time_millis);
+        assert_eq!(0, state.achievement.count("treatments"));
+        assert_eq!(false, state.achievement.flag("insurance_claim"));
+        assert_eq!(msg.end_time, state.end_time);
+        assert_eq!(Timestamp::from_seconds(now.as_secs()), state.start_time);
     }
 
     #[test]
@@ -256,24 +1085,23 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: coin(100000000000, "umed"),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
         assert_eq!(0, response.messages.len());
 
         let update_msg = ExecuteMsg::Update {
-            treatments: Some(1000),
-            insurance_claim: Some(true),
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(1000) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: None,
         };
 
         let result = execute(deps.as_mut(), env, info.clone(), update_msg);
@@ -282,19 +1110,17 @@ mod tests {
         let state = config_read(&mut deps.storage).load().unwrap();
 
         assert_eq!(&info.sender, &state.payer);
-        assert_eq!(&msg.joiner, &state.joiner);
+        assert_eq!(msg.joiner.as_deref(), Some(state.joiner.as_str()));
         assert_eq!(&info.funds, &state.total_amount);
         assert_eq!(1, state.term_of_payments.len());
         assert_eq!(&msg.term_of_payments[0].id, &state.term_of_payments[0].id);
-        assert_eq!(&msg.term_of_payments[0].contract_content.treatments, &state.term_of_payments[0].contract_content.treatments);
-        assert_eq!(&msg.term_of_payments[0].contract_content.insurance_claim, &state.term_of_payments[0].contract_content.insurance_claim);
-        assert_eq!(&msg.term_of_payments[0].contract_content.period_days, &state.term_of_payments[0].contract_content.period_days);
+        assert_eq!(&msg.term_of_payments[0].condition, &state.term_of_payments[0].condition);
         assert_eq!(&msg.term_of_payments[0].amount, &state.term_of_payments[0].amount);
         assert_eq!(&msg.term_of_payments[0].is_payment, &state.term_of_payments[0].is_payment);
-        assert_eq!(1000, state.achievement.treatments);
-        assert_eq!(true, state.achievement.insurance_claim);
-        assert_eq!(msg.end_time_millis, state.end_time_millis);
-        assert_eq!(Some(Timestamp::from_seconds(now.as_secs()).nanos() / 1000), state.start_time_millis);
+        assert_eq!(1000, state.achievement.count("treatments"));
+        assert_eq!(true, state.achievement.flag("insurance_claim"));
+        assert_eq!(msg.end_time, state.end_time);
+        assert_eq!(Timestamp::from_seconds(now.as_secs()), state.start_time);
     }
 
     #[test]
@@ -309,29 +1135,29 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: coin(100000000000, "umed"),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
         assert_eq!(0, response.messages.len());
 
         let update_msg1 = ExecuteMsg::Update {
-            treatments: Some(1000),
-            insurance_claim: None,
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(1000) },
+            ],
+            joiner: None,
         };
 
         let update_msg2 = ExecuteMsg::Update {
-            treatments: None,
-            insurance_claim: Some(true),
+            metrics: vec![
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: None,
         };
 
         let result = execute(deps.as_mut(), env.clone(), info.clone(), update_msg1.clone());
@@ -342,19 +1168,17 @@ mod tests {
         let state = config_read(&mut deps.storage).load().unwrap();
 
         assert_eq!(&info.sender, &state.payer);
-        assert_eq!(&msg.joiner, &state.joiner);
+        assert_eq!(msg.joiner.as_deref(), Some(state.joiner.as_str()));
         assert_eq!(&info.funds, &state.total_amount);
         assert_eq!(1, state.term_of_payments.len());
         assert_eq!(&msg.term_of_payments[0].id, &state.term_of_payments[0].id);
-        assert_eq!(&msg.term_of_payments[0].contract_content.treatments, &state.term_of_payments[0].contract_content.treatments);
-        assert_eq!(&msg.term_of_payments[0].contract_content.insurance_claim, &state.term_of_payments[0].contract_content.insurance_claim);
-        assert_eq!(&msg.term_of_payments[0].contract_content.period_days, &state.term_of_payments[0].contract_content.period_days);
+        assert_eq!(&msg.term_of_payments[0].condition, &state.term_of_payments[0].condition);
         assert_eq!(&msg.term_of_payments[0].amount, &state.term_of_payments[0].amount);
         assert_eq!(&msg.term_of_payments[0].is_payment, &state.term_of_payments[0].is_payment);
-        assert_eq!(1000, state.achievement.treatments);
-        assert_eq!(true, state.achievement.insurance_claim);
-        assert_eq!(msg.end_time_millis, state.end_time_millis);
-        assert_eq!(Some(Timestamp::from_seconds(now.as_secs()).nanos() / 1000), state.start_time_millis);
+        assert_eq!(1000, state.achievement.count("treatments"));
+        assert_eq!(true, state.achievement.flag("insurance_claim"));
+        assert_eq!(msg.end_time, state.end_time);
+        assert_eq!(Timestamp::from_seconds(now.as_secs()), state.start_time);
     }
 
     #[test]
@@ -369,23 +1193,21 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: coin(100000000000, "umed"),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
         assert_eq!(0, response.messages.len());
 
         let append_msg = ExecuteMsg::Append {
-            treatments: Some(100),
+            metric: "treatments".to_string(),
+            n: 100,
+            joiner: None,
         };
         // after 5sec, up 5 height
         env.block.height = env.block.height.clone() + 5;
@@ -400,19 +1222,17 @@ mod tests {
         let appended_state = config_read(&mut deps.storage).load().unwrap();
 
         assert_eq!(&info.sender, &appended_state.payer);
-        assert_eq!(&msg.joiner, &appended_state.joiner);
+        assert_eq!(msg.joiner.as_deref(), Some(appended_state.joiner.as_str()));
         assert_eq!(&info.funds, &appended_state.total_amount);
         assert_eq!(1, appended_state.term_of_payments.len());
         assert_eq!(&msg.term_of_payments[0].id, &appended_state.term_of_payments[0].id);
-        assert_eq!(&msg.term_of_payments[0].contract_content.treatments, &appended_state.term_of_payments[0].contract_content.treatments);
-        assert_eq!(&msg.term_of_payments[0].contract_content.insurance_claim, &appended_state.term_of_payments[0].contract_content.insurance_claim);
-        assert_eq!(&msg.term_of_payments[0].contract_content.period_days, &appended_state.term_of_payments[0].contract_content.period_days);
+        assert_eq!(&msg.term_of_payments[0].condition, &appended_state.term_of_payments[0].condition);
         assert_eq!(&msg.term_of_payments[0].amount, &appended_state.term_of_payments[0].amount);
         assert_eq!(&msg.term_of_payments[0].is_payment, &appended_state.term_of_payments[0].is_payment);
-        assert_eq!(100, appended_state.achievement.treatments);
-        assert_eq!(false, appended_state.achievement.insurance_claim);
-        assert_eq!(msg.end_time_millis, appended_state.end_time_millis);
-        assert_eq!(Some(Timestamp::from_seconds(now.as_secs()).nanos() / 1000), appended_state.start_time_millis);
+        assert_eq!(100, appended_state.achievement.count("treatments"));
+        assert_eq!(false, appended_state.achievement.flag("insurance_claim"));
+        assert_eq!(msg.end_time, appended_state.end_time);
+        assert_eq!(Timestamp::from_seconds(now.as_secs()), appended_state.start_time);
     }
 
     #[test]
@@ -428,16 +1248,12 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: init_amount[0].clone(),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
@@ -445,8 +1261,11 @@ mod tests {
 
         // update achievement
         let update_msg = ExecuteMsg::Update {
-            treatments: Some(100),
-            insurance_claim: Some(true),
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(100) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: None,
         };
         let update_result = execute(deps.as_mut(), env.clone(), info.clone(), update_msg);
         assert_eq!(true, update_result.is_ok());
@@ -485,26 +1304,18 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: coin(100000000000, "umed"),
                 is_payment: false,
             },
             TermOfPayment {
                 id: String::from("id2"),
-                contract_content: ContractContent {
-                    treatments: 500,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(500),
                 amount: coin(100000000000, "umed"),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 15, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 15), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
@@ -514,8 +1325,11 @@ mod tests {
         env.block.height = env.block.height.clone() + 5;
         env.block.time = env.block.time.plus_seconds(5);
         let update_msg = ExecuteMsg::Update {
-            treatments: Some(100),
-            insurance_claim: Some(true),
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(100) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: None,
         };
         let update_result = execute(deps.as_mut(), env.clone(), info.clone(), update_msg);
         assert_eq!(true, update_result.is_ok());
@@ -550,22 +1364,18 @@ mod tests {
         assert_eq!(2, approved_state.term_of_payments.len());
         let payment_1 = &approved_state.term_of_payments[0];
         assert_eq!("id1", payment_1.id);
-        assert_eq!(100, payment_1.contract_content.treatments);
-        assert_eq!(true, payment_1.contract_content.insurance_claim);
-        assert_eq!(0, payment_1.contract_content.period_days);
+        assert_eq!(treatments_and_insurance_claim(100), payment_1.condition);
         assert_eq!(coin(100000000000, "umed"), payment_1.amount);
         assert_eq!(true, payment_1.is_payment);
         let payment_2 = &approved_state.term_of_payments[1];
         assert_eq!("id2", payment_2.id);
-        assert_eq!(500, payment_2.contract_content.treatments);
-        assert_eq!(true, payment_2.contract_content.insurance_claim);
-        assert_eq!(0, payment_2.contract_content.period_days);
+        assert_eq!(treatments_and_insurance_claim(500), payment_2.condition);
         assert_eq!(coin(100000000000, "umed"), payment_2.amount);
         assert_eq!(false, payment_2.is_payment);
-        assert_eq!(100, approved_state.achievement.treatments);
-        assert_eq!(true, approved_state.achievement.insurance_claim);
-        assert_eq!(msg.end_time_millis, approved_state.end_time_millis);
-        assert_eq!(Some(Timestamp::from_seconds(now.as_secs()).nanos() / 1000), approved_state.start_time_millis);
+        assert_eq!(100, approved_state.achievement.count("treatments"));
+        assert_eq!(true, approved_state.achievement.flag("insurance_claim"));
+        assert_eq!(msg.end_time, approved_state.end_time);
+        assert_eq!(Timestamp::from_seconds(now.as_secs()), approved_state.start_time);
     }
 
     #[test]
@@ -581,16 +1391,12 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: init_amount[0].clone(),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
@@ -626,16 +1432,12 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: init_amount[0].clone(),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
@@ -668,16 +1470,12 @@ mod tests {
         let term_of_payments = vec![
             TermOfPayment {
                 id: String::from("id1"),
-                contract_content: ContractContent {
-                    treatments: 100,
-                    insurance_claim: true,
-                    period_days: 0,
-                },
+                condition: treatments_and_insurance_claim(100),
                 amount: init_amount[0].clone(),
                 is_payment: false,
             }
         ];
-        let msg = init_msg(now.as_millis() as u64 + 1000 * 5, term_of_payments.clone());
+        let msg = init_msg(Timestamp::from_seconds(now.as_secs() + 5), term_of_payments.clone());
         let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert_eq!(true, result.is_ok());
         let response = result.unwrap();
@@ -689,9 +1487,1015 @@ mod tests {
         let refund_info = mock_info("payer", &init_amount);
         env.block.height = env.block.height.clone() + 5;
         env.block.time = env.block.time.plus_seconds(5);
-        let refund_result = execute(deps.as_mut(), env.clone(), refund_info.clone(), ExecuteMsg::Refund {});
+        let refund_result = execute(deps.as_mut(), env.clone(), refund_info.clone(), ExecuteMsg::Refund { joiner: None });
         assert_eq!(true, refund_result.is_ok());
 
         println!("{:?}", refund_result);
     }
+
+    #[test]
+    fn execute_program_enrollments() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+        let mut deps = mock_dependencies(&[]);
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(now.as_secs());
+        let info = mock_info("payer", &[]);
+        let msg = InstantiateMsg {
+            joiner: None,
+            term_of_payments: vec![],
+            end_time: None,
+            metrics: None,
+            reporters: None,
+            quorum: None,
+            data_provider: None,
+            challenge: None,
+        };
+        let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert_eq!(true, result.is_ok());
+        match query_joiner(deps.as_ref()).unwrap_err() {
+            StdError::GenericErr { msg } => assert_eq!("Program contract has no single joiner, query 'enrollments'", msg),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: treatments_and_insurance_claim(100),
+                amount: coin(100, "umed"),
+                is_payment: false,
+            }
+        ];
+        let end_time = Some(Timestamp::from_seconds(now.as_secs() + 5));
+        for joiner in ["joiner3", "joiner1", "joiner2"].iter() {
+            let enroll_msg = ExecuteMsg::Enroll { joiner: joiner.to_string(), term_of_payments: term_of_payments.clone(), end_time };
+            let result = execute(deps.as_mut(), env.clone(), mock_info("payer", &coins(100, "umed")), enroll_msg);
+            assert_eq!(true, result.is_ok());
+        }
+
+        let enroll_msg = ExecuteMsg::Enroll { joiner: "joiner1".to_string(), term_of_payments: term_of_payments.clone(), end_time };
+        match execute(deps.as_mut(), env.clone(), mock_info("payer", &coins(100, "umed")), enroll_msg.clone()).unwrap_err() {
+            ContractError::AlreadyEnrolled { joiner } => assert_eq!("joiner1", joiner),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner1", &coins(100, "umed")), enroll_msg).unwrap_err() {
+            ContractError::Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let enrollments = query_enrollments(deps.as_ref(), None, Some(2)).unwrap().enrollments;
+        assert_eq!(vec!["joiner1", "joiner2"], enrollments.iter().map(|e| e.joiner.as_str()).collect::<Vec<&str>>());
+        let enrollments = query_enrollments(deps.as_ref(), Some("joiner2".to_string()), None).unwrap().enrollments;
+        assert_eq!(vec!["joiner3"], enrollments.iter().map(|e| e.joiner.as_str()).collect::<Vec<&str>>());
+
+        // each enrollment has its own achievement
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(100) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: Some("joiner1".to_string()),
+        };
+        let result = execute(deps.as_mut(), env.clone(), info.clone(), update_msg);
+        assert_eq!(true, result.is_ok());
+
+        let result = execute(deps.as_mut(), env.clone(), mock_info("joiner1", &[]), ExecuteMsg::Approve {});
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "joiner1".into(), amount: coins(100, "umed") }), result.unwrap().messages[0]);
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner2", &[]), ExecuteMsg::Approve {}).unwrap_err() {
+            ContractError::NotAchievementContract {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner4", &[]), ExecuteMsg::Approve {}).unwrap_err() {
+            ContractError::NotEnrolled { joiner } => assert_eq!("joiner4", joiner),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // only the unpaid funds of the enrollment are refunded
+        env.block.time = env.block.time.plus_seconds(10);
+        let result = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Refund { joiner: Some("joiner1".to_string()) });
+        assert_eq!(0, result.unwrap().messages.len());
+        let result = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Refund { joiner: Some("joiner2".to_string()) });
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "payer".into(), amount: coins(100, "umed") }), result.unwrap().messages[0]);
+        assert_eq!(1, query_enrollments(deps.as_ref(), None, None).unwrap().enrollments.len());
+    }
+
+    #[test]
+    fn execute_program_refund_with_payouts() {
+        let mut deps = mock_dependencies(&[]);
+        let mut env = mock_env();
+        let info = mock_info("payer", &[]);
+        let msg = InstantiateMsg {
+            joiner: None,
+            term_of_payments: vec![],
+            end_time: None,
+            metrics: None,
+            reporters: None,
+            quorum: None,
+            data_provider: None,
+            challenge: Some(Challenge { period: Duration::Seconds(60), arbiter: "arbiter".to_string() }),
+        };
+        assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg).is_ok());
+
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: treatments_and_insurance_claim(100),
+                amount: coin(100, "umed"),
+                is_payment: false,
+            }
+        ];
+        let end_time = Some(env.block.time.plus_seconds(5));
+        for joiner in ["joiner1", "joiner2"].iter() {
+            let enroll_msg = ExecuteMsg::Enroll { joiner: joiner.to_string(), term_of_payments: term_of_payments.clone(), end_time };
+            assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("payer", &coins(100, "umed")), enroll_msg).is_ok());
+            let update_msg = ExecuteMsg::Update {
+                metrics: vec![
+                    Metric { name: "treatments".to_string(), value: MetricValue::Counter(100) },
+                    Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+                ],
+                joiner: Some(joiner.to_string()),
+            };
+            assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), update_msg).is_ok());
+        }
+
+        // the refund opens the payouts of the achieved terms, and keeps the enrollments until they are closed
+        env.block.time = env.block.time.plus_seconds(10);
+        for joiner in ["joiner1", "joiner2"].iter() {
+            let result = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Refund { joiner: Some(joiner.to_string()) });
+            assert_eq!(0, result.unwrap().messages.len());
+        }
+        assert_eq!(2, query_enrollments(deps.as_ref(), None, None).unwrap().enrollments.len());
+        let dispute_msg = ExecuteMsg::Dispute { id: 1, reason: "reason".to_string(), joiner: Some("joiner2".to_string()) };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), dispute_msg).is_ok());
+
+        // the enrollments are removed when their last payout is closed
+        env.block.time = env.block.time.plus_seconds(60);
+        let result = execute(deps.as_mut(), env.clone(), mock_info("joiner1", &[]), ExecuteMsg::ClaimPayout { id: 1 });
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "joiner1".into(), amount: coins(100, "umed") }), result.unwrap().messages[0]);
+        let enrollments = query_enrollments(deps.as_ref(), None, None).unwrap().enrollments;
+        assert_eq!(vec!["joiner2"], enrollments.iter().map(|e| e.joiner.as_str()).collect::<Vec<&str>>());
+
+        let resolve_msg = ExecuteMsg::ResolveDispute { id: 1, approve: false, joiner: Some("joiner2".to_string()) };
+        let result = execute(deps.as_mut(), env.clone(), mock_info("arbiter", &[]), resolve_msg);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "payer".into(), amount: coins(100, "umed") }), result.unwrap().messages[0]);
+        assert_eq!(0, query_enrollments(deps.as_ref(), None, None).unwrap().enrollments.len());
+    }
+
+    #[test]
+    fn execute_approve_after_period() {
+        let mut deps = mock_dependencies(&[]);
+        let mut env = mock_env();
+        let start_time = env.block.time;
+        let info = mock_info("payer", &coins(100, "umed"));
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: Condition::ElapsedAtLeast { duration: Duration::Days(30) },
+                amount: coin(100, "umed"),
+                is_payment: false,
+            }
+        ];
+        let msg = init_msg(start_time.plus_seconds(Duration::Days(60).seconds().unwrap()), term_of_payments);
+
+        // durations overflowing the time are rejected
+        let overflow = TermOfPayment { condition: Condition::ElapsedAtLeast { duration: Duration::Days(u64::MAX) }, ..msg.term_of_payments[0].clone() };
+        let invalid_msg = InstantiateMsg { term_of_payments: vec![overflow], end_time: None, ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidDuration { duration, .. } => assert_eq!(Duration::Days(u64::MAX), duration),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = InstantiateMsg {
+            challenge: Some(Challenge { period: Duration::Seconds(u64::MAX), arbiter: "arbiter".to_string() }),
+            ..msg.clone()
+        };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidDuration { duration, msg } => assert_eq!((Duration::Seconds(u64::MAX), "overflow"), (duration, msg.as_str())),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = InstantiateMsg {
+            challenge: Some(Challenge { period: Duration::Days(0), arbiter: "arbiter".to_string() }),
+            ..msg.clone()
+        };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidDuration { duration, .. } => assert_eq!(Duration::Days(0), duration),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert_eq!(true, result.is_ok());
+
+        // one second before the period ends
+        env.block.time = start_time.plus_seconds(30 * 24 * 60 * 60 - 1);
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), ExecuteMsg::Approve {}).unwrap_err() {
+            ContractError::NotAchievementContract {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.time = start_time.plus_seconds(30 * 24 * 60 * 60);
+        let result = execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), ExecuteMsg::Approve {});
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "joiner".into(), amount: coins(100, "umed") }), result.unwrap().messages[0]);
+    }
+
+    #[test]
+    fn execute_after_end_time() {
+        let mut deps = mock_dependencies(&[]);
+        let mut env = mock_env();
+        let end_time = env.block.time.plus_seconds(10);
+        let info = mock_info("payer", &coins(100, "umed"));
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: Condition::MinCount { metric: "treatments".to_string(), n: 100 },
+                amount: coin(100, "umed"),
+                is_payment: false,
+            }
+        ];
+        let result = instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg(end_time, term_of_payments));
+        assert_eq!(true, result.is_ok());
+
+        let append_msg = ExecuteMsg::Append { metric: "treatments".to_string(), n: 1, joiner: None };
+        env.block.time = end_time;
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), append_msg.clone()).is_ok());
+        match execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Refund { joiner: None }).unwrap_err() {
+            ContractError::NotExpired {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.time = Timestamp::from_nanos(end_time.nanos() + 1);
+        match execute(deps.as_mut(), env.clone(), info.clone(), append_msg).unwrap_err() {
+            ContractError::Expired { end_time: expired } => assert_eq!(Some(end_time), expired),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Refund { joiner: None }).is_ok());
+    }
+
+    #[test]
+    fn achievement_conditions() {
+        let mut env = mock_env();
+        let start_time = env.block.time;
+        let achievement = Achievement {
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(10) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+        };
+
+        let min_count = |n| Condition::MinCount { metric: "treatments".to_string(), n };
+        let insurance_claim = Condition::Flag { name: "insurance_claim".to_string() };
+        let elapsed = Condition::ElapsedAtLeast { duration: Duration::Days(7) };
+
+        assert_eq!(true, achievement.is_achievement(&min_count(10), start_time, &env));
+        assert_eq!(false, achievement.is_achievement(&min_count(11), start_time, &env));
+        assert_eq!(false, achievement.is_achievement(&Condition::MinCount { metric: "steps".to_string(), n: 1 }, start_time, &env));
+        assert_eq!(false, achievement.is_achievement(&Condition::Flag { name: "check_up".to_string() }, start_time, &env));
+        assert_eq!(false, achievement.is_achievement(&Condition::Not(Box::new(insurance_claim.clone())), start_time, &env));
+
+        // the insurance claim does not skip the period
+        let all = Condition::All(vec![insurance_claim.clone(), elapsed.clone()]);
+        assert_eq!(false, achievement.is_achievement(&all, start_time, &env));
+        let any = Condition::Any(vec![min_count(11), elapsed.clone()]);
+        assert_eq!(false, achievement.is_achievement(&any, start_time, &env));
+
+        env.block.time = start_time.plus_seconds(Duration::Days(7).seconds().unwrap());
+        assert_eq!(true, achievement.is_achievement(&all, start_time, &env));
+        assert_eq!(true, achievement.is_achievement(&any, start_time, &env));
+        assert_eq!(true, achievement.is_achievement(&Condition::All(vec![]), start_time, &env));
+        assert_eq!(false, achievement.is_achievement(&Condition::Any(vec![]), start_time, &env));
+
+        // nested conditions are validated against the kinds of the metrics
+        assert_eq!(true, achievement.validate_condition(&Condition::Not(Box::new(all.clone())), start_time).is_ok());
+        let invalid_conditions = vec![
+            (Condition::Any(vec![elapsed.clone(), Condition::Flag { name: "treatments".to_string() }]), "treatments"),
+            (Condition::Not(Box::new(Condition::MinCount { metric: "insurance_claim".to_string(), n: 1 })), "insurance_claim"),
+            (Condition::All(vec![all, Condition::Any(vec![min_count(1), Condition::Flag { name: "check_up".to_string() }])]), "check_up"),
+        ];
+        for (condition, metric) in invalid_conditions {
+            match achievement.validate_condition(&condition, start_time).unwrap_err() {
+                ContractError::InvalidMetric { name, .. } => assert_eq!(metric, name),
+                e => panic!("unexpected error: {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn execute_named_metrics() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("payer", &coins(100, "umed"));
+        let metrics = vec![
+            MetricSpec { name: "steps".to_string(), kind: MetricKind::Counter },
+            MetricSpec { name: "check_up".to_string(), kind: MetricKind::Boolean },
+            MetricSpec { name: "lab_result".to_string(), kind: MetricKind::LatestValue },
+        ];
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: Condition::All(vec![
+                    Condition::MinCount { metric: "steps".to_string(), n: 10000 },
+                    Condition::Flag { name: "check_up".to_string() },
+                    Condition::MinCount { metric: "lab_result".to_string(), n: 80 },
+                ]),
+                amount: coin(100, "umed"),
+                is_payment: false,
+            }
+        ];
+        let msg = InstantiateMsg {
+            metrics: Some(metrics.clone()),
+            ..init_msg(env.block.time.plus_seconds(10), term_of_payments.clone())
+        };
+
+        // the conditions must refer to the declared metrics
+        let invalid_msg = InstantiateMsg { metrics: None, ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("steps", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let mut duplicated = metrics.clone();
+        duplicated.push(MetricSpec { name: "steps".to_string(), kind: MetricKind::Boolean });
+        let invalid_msg = InstantiateMsg { metrics: Some(duplicated), ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("steps", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let result = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert_eq!(true, result.is_ok());
+
+        let append_msg = |metric: &str, n| ExecuteMsg::Append { metric: metric.to_string(), n, joiner: None };
+        match execute(deps.as_mut(), env.clone(), info.clone(), append_msg("check_up", 1)).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("check_up", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![Metric { name: "lab_result".to_string(), value: MetricValue::Boolean(true) }],
+            joiner: None,
+        };
+        match execute(deps.as_mut(), env.clone(), info.clone(), update_msg).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("lab_result", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![Metric { name: "weight".to_string(), value: MetricValue::LatestValue(70) }],
+            joiner: None,
+        };
+        match execute(deps.as_mut(), env.clone(), info.clone(), update_msg).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("weight", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), append_msg("steps", 6000)).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), append_msg("steps", 6000)).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), append_msg("steps", 4000)).is_ok());
+        match execute(deps.as_mut(), env.clone(), info.clone(), append_msg("steps", u64::MAX)).unwrap_err() {
+            ContractError::InvalidMetric { name, msg } => assert_eq!(("steps", "overflow"), (name.as_str(), msg.as_str())),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![
+                Metric { name: "check_up".to_string(), value: MetricValue::Boolean(true) },
+                Metric { name: "lab_result".to_string(), value: MetricValue::LatestValue(90) },
+            ],
+            joiner: None,
+        };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), update_msg).is_ok());
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![Metric { name: "lab_result".to_string(), value: MetricValue::LatestValue(70) }],
+            joiner: None,
+        };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), update_msg).is_ok());
+
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(Some(MetricValue::Counter(10000)), state.achievement.value("steps"));
+        assert_eq!(Some(MetricValue::LatestValue(70)), state.achievement.value("lab_result"));
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), ExecuteMsg::Approve {}).unwrap_err() {
+            ContractError::NotAchievementContract {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn instantiate_validation() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("payer", &coins(300, "umed"));
+        let term = |id: &str, condition: Condition, amount: Coin| TermOfPayment {
+            id: id.to_string(),
+            condition,
+            amount,
+            is_payment: false,
+        };
+        let term_of_payments = vec![
+            term("id1", treatments_and_insurance_claim(100), coin(100, "umed")),
+            term("id2", Condition::ElapsedAtLeast { duration: Duration::Days(1) }, coin(200, "umed")),
+        ];
+        let end_time = env.block.time.plus_seconds(Duration::Days(2).seconds().unwrap());
+        let msg = init_msg(end_time, term_of_payments.clone());
+
+        let invalid_msg = init_msg(end_time, vec![]);
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::EmptyTermOfPayments {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let mut duplicated = term_of_payments.clone();
+        duplicated[1].id = "id1".to_string();
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg(end_time, duplicated)).unwrap_err() {
+            ContractError::DuplicatedTermOfPayment { id } => assert_eq!("id1", id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let mut paid = term_of_payments.clone();
+        paid[0].is_payment = true;
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg(end_time, paid)).unwrap_err() {
+            ContractError::PaidTermOfPayment { id } => assert_eq!("id1", id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let mut zero = term_of_payments.clone();
+        zero[1].amount = coin(0, "umed");
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg(end_time, zero)).unwrap_err() {
+            ContractError::ZeroAmount { id } => assert_eq!("id2", id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = init_msg(env.block.time.plus_seconds(3600), term_of_payments.clone());
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::UnreachableTermOfPayment { id, .. } => assert_eq!("id2", id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let mut overflow = term_of_payments.clone();
+        overflow[0].amount = coin(u128::MAX, "umed");
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg(end_time, overflow)).unwrap_err() {
+            ContractError::Std(StdError::Overflow { .. }) => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let short_info = mock_info("payer", &coins(299, "umed"));
+        match instantiate(deps.as_mut(), env.clone(), short_info, msg.clone()).unwrap_err() {
+            ContractError::InsufficientFunds { required, funds } => {
+                assert_eq!(coin(300, "umed"), required);
+                assert_eq!(coin(299, "umed"), funds);
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let other_info = mock_info("payer", &[coin(300, "umed"), coin(1, "uatom")]);
+        match instantiate(deps.as_mut(), env.clone(), other_info, msg.clone()).unwrap_err() {
+            ContractError::UnexpectedDenom { denom } => assert_eq!("uatom", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg).is_ok());
+    }
+
+    #[test]
+    fn execute_top_up_and_amendment() {
+        let mut deps = mock_dependencies(&[]);
+        let mut env = mock_env();
+        let payer_info = mock_info("payer", &coins(300, "umed"));
+        let joiner_info = mock_info("joiner", &[]);
+        let term = |id: &str, treatments: u64, amount: u128| TermOfPayment {
+            id: id.to_string(),
+            condition: treatments_and_insurance_claim(treatments),
+            amount: coin(amount, "umed"),
+            is_payment: false,
+        };
+        let end_time = env.block.time.plus_seconds(10);
+        let msg = init_msg(end_time, vec![term("id1", 100, 100), term("id2", 500, 200)]);
+        assert_eq!(true, instantiate(deps.as_mut(), env.clone(), payer_info.clone(), msg).is_ok());
+
+        // top up
+        let top_up_msg = ExecuteMsg::TopUp { joiner: None };
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner", &coins(100, "umed")), top_up_msg.clone()).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), mock_info("payer", &[]), top_up_msg.clone()).unwrap_err() {
+            ContractError::NoFunds {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("payer", &coins(100, "umed")), top_up_msg.clone()).is_ok());
+        assert_eq!(coins(400, "umed"), config_read(&deps.storage).load().unwrap().total_amount);
+        match execute(deps.as_mut(), env.clone(), mock_info("payer", &coins(u128::MAX, "umed")), top_up_msg).unwrap_err() {
+            ContractError::Std(StdError::Overflow { .. }) => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the amended terms must be covered by the escrow
+        let propose_msg = |amendment: Amendment| ExecuteMsg::ProposeAmendment { amendment, joiner: None };
+        let no_change = Amendment { add: vec![], remove: vec![], reprice: vec![], end_time: None };
+        let invalid_amendment = Amendment {
+            reprice: vec![Reprice { id: "id1".to_string(), amount: coin(300, "umed") }],
+            ..no_change.clone()
+        };
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), propose_msg(invalid_amendment)).unwrap_err() {
+            ContractError::InsufficientFunds { required, .. } => assert_eq!(coin(500, "umed"), required),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_amendment = Amendment { remove: vec!["id3".to_string()], ..no_change.clone() };
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), propose_msg(invalid_amendment)).unwrap_err() {
+            ContractError::UnknownTermOfPayment { id } => assert_eq!("id3", id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_amendment = Amendment { end_time: Some(env.block.time.plus_seconds(5)), ..no_change.clone() };
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), propose_msg(invalid_amendment)).unwrap_err() {
+            ContractError::InvalidAmendment { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let amendment = Amendment {
+            add: vec![term("id3", 300, 200)],
+            remove: vec!["id2".to_string()],
+            reprice: vec![Reprice { id: "id1".to_string(), amount: coin(150, "umed") }],
+            end_time: Some(env.block.time.plus_seconds(20)),
+        };
+        match execute(deps.as_mut(), env.clone(), mock_info("other", &[]), propose_msg(amendment.clone())).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let propose_response = execute(deps.as_mut(), env.clone(), payer_info.clone(), propose_msg(amendment.clone())).unwrap();
+        assert_eq!("1", propose_response.attributes[1].value);
+
+        // only the other party can accept the pending amendment
+        let accept_msg = |id: u64| ExecuteMsg::AcceptAmendment { id, joiner: None };
+        match execute(deps.as_mut(), env.clone(), payer_info.clone(), accept_msg(1)).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), mock_info("other", &[]), accept_msg(1)).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), accept_msg(2)).unwrap_err() {
+            ContractError::InvalidAmendment { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), joiner_info.clone(), accept_msg(1)).is_ok());
+
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(vec![term("id1", 100, 150), term("id3", 300, 200)], state.term_of_payments);
+        assert_eq!(Some(env.block.time.plus_seconds(20)), state.end_time);
+        let amendments = query_amendments(deps.as_ref(), None).unwrap();
+        assert_eq!(None, amendments.pending);
+        assert_eq!(1, amendments.amendments.len());
+        assert_eq!(Addr::unchecked("payer"), amendments.amendments[0].proposer);
+        assert_eq!(amendment, amendments.amendments[0].amendment);
+        assert_eq!(Some(env.block.time), amendments.amendments[0].accepted_at);
+
+        // paid terms cannot be amended
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(100) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: None,
+        };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), payer_info.clone(), update_msg).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::Approve {}).is_ok());
+        let invalid_amendment = Amendment { remove: vec!["id1".to_string()], ..no_change.clone() };
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), propose_msg(invalid_amendment)).unwrap_err() {
+            ContractError::PaidTermOfPayment { id } => assert_eq!("id1", id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the next proposal gets the next id
+        let propose_response = execute(deps.as_mut(), env.clone(), joiner_info.clone(), propose_msg(no_change)).unwrap();
+        assert_eq!("2", propose_response.attributes[1].value);
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), accept_msg(2)).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.time = env.block.time.plus_seconds(30);
+        match execute(deps.as_mut(), env.clone(), payer_info.clone(), accept_msg(2)).unwrap_err() {
+            ContractError::Expired { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn execute_refund_settlement() {
+        let mut deps = mock_dependencies(&coins(300, "umed"));
+        let mut env = mock_env();
+        let info = mock_info("payer", &coins(300, "umed"));
+        let end_time = env.block.time.plus_seconds(10);
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: treatments_and_insurance_claim(100),
+                amount: coin(100, "umed"),
+                is_payment: false,
+            },
+            TermOfPayment {
+                id: String::from("id2"),
+                condition: treatments_and_insurance_claim(500),
+                amount: coin(200, "umed"),
+                is_payment: false,
+            }
+        ];
+        let result = instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg(end_time, term_of_payments));
+        assert_eq!(true, result.is_ok());
+
+        // only the funds exceeding the unpaid terms are refunded early
+        let refund_excess_msg = ExecuteMsg::RefundExcess { joiner: None };
+        match execute(deps.as_mut(), env.clone(), info.clone(), refund_excess_msg.clone()).unwrap_err() {
+            ContractError::NoFunds {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let top_up_msg = ExecuteMsg::TopUp { joiner: None };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("payer", &coins(50, "umed")), top_up_msg).is_ok());
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), refund_excess_msg.clone()).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let result = execute(deps.as_mut(), env.clone(), info.clone(), refund_excess_msg);
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "payer".into(), amount: coins(50, "umed") }), result.unwrap().messages[0]);
+        assert_eq!(coins(300, "umed"), config_read(&deps.storage).load().unwrap().total_amount);
+
+        // the joiner achieves id1 but does not approve it before end_time
+        let update_msg = ExecuteMsg::Update {
+            metrics: vec![
+                Metric { name: "treatments".to_string(), value: MetricValue::Counter(100) },
+                Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) },
+            ],
+            joiner: None,
+        };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), update_msg).is_ok());
+        match execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::Refund { joiner: None }).unwrap_err() {
+            ContractError::NotExpired {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        env.block.time = end_time.plus_seconds(1);
+        match execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::RefundExcess { joiner: None }).unwrap_err() {
+            ContractError::Expired { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let response = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::Refund { joiner: None }).unwrap();
+        assert_eq!(vec![
+            CosmosMsg::Bank(BankMsg::Send { to_address: "joiner".into(), amount: coins(100, "umed") }),
+            CosmosMsg::Bank(BankMsg::Send { to_address: "payer".into(), amount: coins(200, "umed") }),
+        ], response.messages);
+        let state = config_read(&deps.storage).load().unwrap();
+        assert_eq!(true, state.term_of_payments[0].is_payment);
+        assert_eq!(false, state.term_of_payments[1].is_payment);
+    }
+
+    #[test]
+    fn execute_report_quorum() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let info = mock_info("payer", &coins(100, "umed"));
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: treatments_and_insurance_claim(100),
+                amount: coin(100, "umed"),
+                is_payment: false,
+            }
+        ];
+        let reporter = |address: &str, metrics: &[&str]| Reporter {
+            address: address.to_string(),
+            metrics: metrics.iter().map(|m| m.to_string()).collect(),
+        };
+        let msg = InstantiateMsg {
+            reporters: Some(vec![
+                reporter("hospital", &["treatments", "insurance_claim"]),
+                reporter("clinic", &["treatments"]),
+                reporter("insurer", &["insurance_claim"]),
+            ]),
+            quorum: Some(2),
+            ..init_msg(env.block.time.plus_seconds(10), term_of_payments)
+        };
+
+        let invalid_msg = InstantiateMsg { quorum: Some(3), ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidReporters { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = InstantiateMsg { reporters: Some(vec![reporter("hospital", &["steps"])]), quorum: None, ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("steps", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = InstantiateMsg { quorum: Some(0), ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidReporters { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = InstantiateMsg { reporters: Some(vec![reporter("hospital", &["treatments"]), reporter("hospital", &["insurance_claim"])]), quorum: None, ..msg.clone() };
+        match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+            ContractError::InvalidReporters { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone()).is_ok());
+
+        // the payer is not the judge anymore
+        let append_msg = ExecuteMsg::Append { metric: "treatments".to_string(), n: 100, joiner: None };
+        match execute(deps.as_mut(), env.clone(), info.clone(), append_msg).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let treatments = |n: u64| Metric { name: "treatments".to_string(), value: MetricValue::Counter(n) };
+        let report_msg = |metric: Metric, evidence_hash: &str| ExecuteMsg::Report {
+            metric,
+            evidence_hash: evidence_hash.to_string(),
+            joiner: None,
+        };
+        match execute(deps.as_mut(), env.clone(), mock_info("insurer", &[]), report_msg(treatments(100), "hash")).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), mock_info("hospital", &[]), report_msg(treatments(100), "")).unwrap_err() {
+            ContractError::InvalidReport { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let boolean_treatments = Metric { name: "treatments".to_string(), value: MetricValue::Boolean(true) };
+        match execute(deps.as_mut(), env.clone(), mock_info("hospital", &[]), report_msg(boolean_treatments, "hash")).unwrap_err() {
+            ContractError::InvalidMetric { name, .. } => assert_eq!("treatments", name),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let mut expired_env = env.clone();
+        expired_env.block.time = env.block.time.plus_seconds(11);
+        match execute(deps.as_mut(), expired_env, mock_info("hospital", &[]), report_msg(treatments(100), "hash")).unwrap_err() {
+            ContractError::Expired { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // a value is accepted when the quorum of reporters agrees on it
+        let response = execute(deps.as_mut(), env.clone(), mock_info("hospital", &[]), report_msg(treatments(100), "hash1")).unwrap();
+        assert_eq!("false", response.attributes[2].value);
+        let response = execute(deps.as_mut(), env.clone(), mock_info("clinic", &[]), report_msg(treatments(90), "hash2")).unwrap();
+        assert_eq!("false", response.attributes[2].value);
+        let reports = query_reports(deps.as_ref(), None).unwrap().reports;
+        assert_eq!(2, reports.len());
+        assert_eq!(Addr::unchecked("clinic"), reports[1].reporter);
+        assert_eq!("hash2", reports[1].evidence_hash);
+        assert_eq!(env.block.time, reports[1].reported_at);
+
+        // a later report replaces the previous report of the reporter, and the agreeing reports are kept as accepted
+        let mut accepted_env = env.clone();
+        accepted_env.block.time = env.block.time.plus_seconds(1);
+        let response = execute(deps.as_mut(), accepted_env.clone(), mock_info("clinic", &[]), report_msg(treatments(100), "hash3")).unwrap();
+        assert_eq!("true", response.attributes[2].value);
+        assert_eq!("hash1,hash3", response.attributes[3].value);
+        let reports = query_reports(deps.as_ref(), None).unwrap();
+        assert_eq!(0, reports.reports.len());
+        assert_eq!(vec![
+            Report { reporter: Addr::unchecked("hospital"), metric: treatments(100), evidence_hash: "hash1".to_string(), reported_at: env.block.time },
+            Report { reporter: Addr::unchecked("clinic"), metric: treatments(100), evidence_hash: "hash3".to_string(), reported_at: accepted_env.block.time },
+        ], reports.accepted_reports);
+
+        match execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), ExecuteMsg::Approve {}).unwrap_err() {
+            ContractError::NotAchievementContract {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let insurance_claim = Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("hospital", &[]), report_msg(insurance_claim.clone(), "hash4")).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("insurer", &[]), report_msg(insurance_claim, "hash5")).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), ExecuteMsg::Approve {}).is_ok());
+
+        // the payer still sets the metrics without reporters
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg { reporters: Some(vec![reporter("clinic", &["treatments"])]), quorum: None, end_time: None, ..msg };
+        assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg).is_ok());
+        let update_msg = |metric: Metric| ExecuteMsg::Update { metrics: vec![metric], joiner: None };
+        match execute(deps.as_mut(), env.clone(), info.clone(), update_msg(treatments(100))).unwrap_err() {
+            Unauthorized { msg } => assert_eq!("'treatments' is reported by the registered reporters", msg),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let insurance_claim = Metric { name: "insurance_claim".to_string(), value: MetricValue::Boolean(true) };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), update_msg(insurance_claim)).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("clinic", &[]), report_msg(treatments(100), "hash")).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("joiner", &[]), ExecuteMsg::Approve {}).is_ok());
+    }
+
+    #[test]
+    fn execute_claim_achievement() {
+        let ed25519_key = ed25519_zebra::SigningKey::from([7u8; 32]);
+        let secp256k1_key = k256::ecdsa::SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let sign = |scheme: SignatureScheme, payload: &ClaimPayload| -> Binary {
+            let message = to_vec(payload).unwrap();
+            match scheme {
+                SignatureScheme::Ed25519 => Binary::from(<[u8; 64]>::from(ed25519_key.sign(&message)).to_vec()),
+                SignatureScheme::Secp256k1 => {
+                    let signature: k256::ecdsa::Signature = secp256k1_key.sign(&message);
+                    Binary::from(signature.as_ref())
+                }
+            }
+        };
+        let providers = vec![
+            DataProvider {
+                scheme: SignatureScheme::Ed25519,
+                public_key: Binary::from(<[u8; 32]>::from(ed25519_zebra::VerificationKeyBytes::from(&ed25519_key)).to_vec()),
+            },
+            DataProvider {
+                scheme: SignatureScheme::Secp256k1,
+                public_key: Binary::from(secp256k1_key.verify_key().to_bytes().to_vec()),
+            },
+        ];
+
+        for provider in providers {
+            let mut deps = mock_dependencies(&[]);
+            let env = mock_env();
+            let info = mock_info("payer", &coins(100, "umed"));
+            let joiner_info = mock_info("joiner", &[]);
+            let term_of_payments = vec![
+                TermOfPayment {
+                    id: String::from("id1"),
+                    condition: Condition::MinCount { metric: "treatments".to_string(), n: 100 },
+                    amount: coin(100, "umed"),
+                    is_payment: false,
+                }
+            ];
+            let msg = init_msg(env.block.time.plus_seconds(10), term_of_payments);
+
+            let invalid_key = DataProvider { public_key: Binary::from(vec![1u8; 20]), ..provider.clone() };
+            let invalid_msg = InstantiateMsg { data_provider: Some(invalid_key), ..msg.clone() };
+            match instantiate(deps.as_mut(), env.clone(), info.clone(), invalid_msg).unwrap_err() {
+                ContractError::InvalidDataProvider { .. } => {}
+                e => panic!("unexpected error: {:?}", e),
+            }
+            let msg = InstantiateMsg { data_provider: Some(provider.clone()), ..msg };
+            assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone()).is_ok());
+
+            let payload = ClaimPayload {
+                contract: env.contract.address.to_string(),
+                joiner: "joiner".to_string(),
+                metric: "treatments".to_string(),
+                value: MetricValue::Counter(100),
+                evidence_hash: "hash".to_string(),
+                nonce: 1,
+            };
+            let claim_msg = |payload: &ClaimPayload, signature: Binary| ExecuteMsg::ClaimAchievement {
+                metric: payload.metric.clone(),
+                value: payload.value,
+                evidence_hash: payload.evidence_hash.clone(),
+                nonce: payload.nonce,
+                signature,
+            };
+
+            // the signature covers the value and the joiner
+            let tampered = ClaimPayload { value: MetricValue::Counter(1000), ..payload.clone() };
+            match execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&tampered, sign(provider.scheme, &payload))).unwrap_err() {
+                ContractError::InvalidSignature {} => {}
+                e => panic!("unexpected error: {:?}", e),
+            }
+            let other = ClaimPayload { joiner: "other".to_string(), ..payload.clone() };
+            match execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&payload, sign(provider.scheme, &other))).unwrap_err() {
+                ContractError::InvalidSignature {} => {}
+                e => panic!("unexpected error: {:?}", e),
+            }
+            match execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&payload, Binary::from(vec![1u8; 64]))).unwrap_err() {
+                ContractError::InvalidSignature {} => {}
+                e => panic!("unexpected error: {:?}", e),
+            }
+
+            let result = execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&payload, sign(provider.scheme, &payload)));
+            assert_eq!(true, result.is_ok());
+            assert_eq!(1, config_read(&deps.storage).load().unwrap().claim_nonce);
+
+            // a claim cannot be replayed
+            match execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&payload, sign(provider.scheme, &payload))).unwrap_err() {
+                ContractError::InvalidNonce { nonce, last } => assert_eq!((1, 1), (nonce, last)),
+                e => panic!("unexpected error: {:?}", e),
+            }
+            let stale = ClaimPayload { nonce: 0, ..payload.clone() };
+            match execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&stale, sign(provider.scheme, &stale))).unwrap_err() {
+                ContractError::InvalidNonce { nonce, last } => assert_eq!((0, 1), (nonce, last)),
+                e => panic!("unexpected error: {:?}", e),
+            }
+
+            let approve_result = execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::Approve {});
+            assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "joiner".into(), amount: coins(100, "umed") }), approve_result.unwrap().messages[0]);
+
+            // with registered reporters, a claim cannot bypass their quorum
+            let mut deps = mock_dependencies(&[]);
+            let msg = InstantiateMsg {
+                reporters: Some(vec![
+                    Reporter { address: "reporter1".to_string(), metrics: vec!["treatments".to_string()] },
+                    Reporter { address: "reporter2".to_string(), metrics: vec!["treatments".to_string()] },
+                ]),
+                quorum: Some(2),
+                ..msg
+            };
+            assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg).is_ok());
+            match execute(deps.as_mut(), env.clone(), joiner_info.clone(), claim_msg(&payload, sign(provider.scheme, &payload))).unwrap_err() {
+                Unauthorized { .. } => {}
+                e => panic!("unexpected error: {:?}", e),
+            }
+            assert_eq!(0, config_read(&deps.storage).load().unwrap().claim_nonce);
+        }
+    }
+
+    #[test]
+    fn execute_challenge_period() {
+        let mut deps = mock_dependencies(&coins(300, "umed"));
+        let mut env = mock_env();
+        let start_time = env.block.time;
+        let info = mock_info("payer", &coins(300, "umed"));
+        let joiner_info = mock_info("joiner", &[]);
+        let term_of_payments = vec![
+            TermOfPayment {
+                id: String::from("id1"),
+                condition: Condition::MinCount { metric: "treatments".to_string(), n: 100 },
+                amount: coin(100, "umed"),
+                is_payment: false,
+            },
+            TermOfPayment {
+                id: String::from("id2"),
+                condition: Condition::MinCount { metric: "treatments".to_string(), n: 500 },
+                amount: coin(200, "umed"),
+                is_payment: false,
+            }
+        ];
+        let msg = InstantiateMsg {
+            challenge: Some(Challenge { period: Duration::Days(3), arbiter: "arbiter".to_string() }),
+            ..init_msg(start_time.plus_seconds(Duration::Days(10).seconds().unwrap()), term_of_payments)
+        };
+
+        // without a challenge period, nothing can be disputed
+        let mut other_deps = mock_dependencies(&coins(300, "umed"));
+        assert_eq!(true, instantiate(other_deps.as_mut(), env.clone(), info.clone(), InstantiateMsg { challenge: None, ..msg.clone() }).is_ok());
+        let dispute_msg = ExecuteMsg::Dispute { id: 1, reason: "fraud".to_string(), joiner: None };
+        match execute(other_deps.as_mut(), env.clone(), info.clone(), dispute_msg).unwrap_err() {
+            ContractError::NoChallengePeriod {} => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let invalid_msg = InstantiateMsg { challenge: Some(Challenge { period: Duration::Days(3), arbiter: "x".to_string() }), ..msg.clone() };
+        assert_eq!(true, instantiate(other_deps.as_mut(), env.clone(), info.clone(), invalid_msg).is_err());
+
+        assert_eq!(true, instantiate(deps.as_mut(), env.clone(), info.clone(), msg).is_ok());
+
+        // approval opens a pending payout
+        let append_msg = |n| ExecuteMsg::Append { metric: "treatments".to_string(), n, joiner: None };
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), append_msg(100)).is_ok());
+        let response = execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::Approve {}).unwrap();
+        assert_eq!(0, response.messages.len());
+        assert_eq!("1", response.attributes[1].value);
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::ClaimPayout { id: 1 }).unwrap_err() {
+            ContractError::ChallengePeriodNotEnded { id } => assert_eq!(1, id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::ClaimPayout { id: 1 }).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::ClaimPayout { id: 3 }).unwrap_err() {
+            ContractError::InvalidPayout { id, .. } => assert_eq!(3, id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the payer disputes within the period
+        let dispute_msg = |id: u64, reason: &str| ExecuteMsg::Dispute { id, reason: reason.to_string(), joiner: None };
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), dispute_msg(1, "fraud")).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), info.clone(), dispute_msg(1, "")).unwrap_err() {
+            ContractError::InvalidPayout { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        match execute(deps.as_mut(), env.clone(), info.clone(), dispute_msg(3, "fraud")).unwrap_err() {
+            ContractError::InvalidPayout { id, .. } => assert_eq!(3, id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), dispute_msg(1, "fraud")).is_ok());
+        let payouts = query_payouts(deps.as_ref(), None).unwrap().payouts;
+        assert_eq!(PayoutStatus::Disputed, payouts[0].status);
+        assert_eq!(Some("fraud".to_string()), payouts[0].dispute_reason);
+        match execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::ClaimPayout { id: 1 }).unwrap_err() {
+            ContractError::InvalidPayout { id, .. } => assert_eq!(1, id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the arbiter resolves the dispute
+        let resolve_msg = ExecuteMsg::ResolveDispute { id: 1, approve: false, joiner: None };
+        match execute(deps.as_mut(), env.clone(), info.clone(), resolve_msg.clone()).unwrap_err() {
+            Unauthorized { .. } => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let response = execute(deps.as_mut(), env.clone(), mock_info("arbiter", &[]), resolve_msg.clone()).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "payer".into(), amount: coins(100, "umed") }), response.messages[0]);
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), mock_info("arbiter", &[]), resolve_msg).is_err());
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(200, "umed"));
+
+        // an undisputed payout is claimable after the period
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), info.clone(), append_msg(400)).is_ok());
+        assert_eq!(true, execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::Approve {}).is_ok());
+        env.block.time = env.block.time.plus_seconds(Duration::Days(3).seconds().unwrap());
+        match execute(deps.as_mut(), env.clone(), info.clone(), dispute_msg(2, "fraud")).unwrap_err() {
+            ContractError::ChallengePeriodEnded { id } => assert_eq!(2, id),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the funds of the pending payout are not refunded on expiry
+        env.block.time = start_time.plus_seconds(Duration::Days(11).seconds().unwrap());
+        let response = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Refund { joiner: None }).unwrap();
+        assert_eq!(0, response.messages.len());
+
+        let response = execute(deps.as_mut(), env.clone(), joiner_info.clone(), ExecuteMsg::ClaimPayout { id: 2 }).unwrap();
+        assert_eq!(CosmosMsg::Bank(BankMsg::Send { to_address: "joiner".into(), amount: coins(200, "umed") }), response.messages[0]);
+        let payouts = query_payouts(deps.as_ref(), None).unwrap().payouts;
+        assert_eq!(vec![PayoutStatus::Rejected, PayoutStatus::Paid], payouts.iter().map(|p| p.status).collect::<Vec<PayoutStatus>>());
+    }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, StdError, Timestamp};
 use thiserror::Error;
 
+use crate::msg::Duration;
+
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")]
@@ -13,13 +15,19 @@ pub enum ContractError {
 
     #[error("Escrow expired (end_time {end_time:?})")]
     Expired {
-        end_time: Option<u64>,
+        end_time: Option<Timestamp>,
     },
 
-    #[error("Escrow invalid parameter. treatments or period_days greater then 0. (treatments {treatments:?} period_days {period_days:?})")]
+    #[error("Escrow invalid parameter. treatments or period greater then 0. (treatments {treatments:?} period {period:?})")]
     InvalidParameter {
         treatments: Option<u64>,
-        period_days: Option<u64>,
+        period: Option<Duration>,
+    },
+
+    #[error("Invalid duration. {msg:?} (duration {duration})")]
+    InvalidDuration {
+        duration: Duration,
+        msg: String,
     },
 
     #[error("Not achievement contract.")]
@@ -27,4 +35,116 @@ pub enum ContractError {
 
     #[error("Escrow not expired")]
     NotExpired {},
+
+    #[error("Invalid metric. {msg:?} (name {name:?})")]
+    InvalidMetric {
+        name: String,
+        msg: String,
+    },
+
+    #[error("No term of payments")]
+    EmptyTermOfPayments {},
+
+    #[error("Duplicated term of payment (id {id:?})")]
+    DuplicatedTermOfPayment {
+        id: String,
+    },
+
+    #[error("Term of payment is already paid (id {id:?})")]
+    PaidTermOfPayment {
+        id: String,
+    },
+
+    #[error("Zero amount of term of payment (id {id:?})")]
+    ZeroAmount {
+        id: String,
+    },
+
+    #[error("Insufficient funds (required {required}, funds {funds})")]
+    InsufficientFunds {
+        required: Coin,
+        funds: Coin,
+    },
+
+    #[error("Funds of a denom not paid by any term of payment (denom {denom:?})")]
+    UnexpectedDenom {
+        denom: String,
+    },
+
+    #[error("Term of payment cannot be achieved before end_time (id {id:?} end_time {end_time})")]
+    UnreachableTermOfPayment {
+        id: String,
+        end_time: Timestamp,
+    },
+
+    #[error("No funds")]
+    NoFunds {},
+
+    #[error("Unknown term of payment (id {id:?})")]
+    UnknownTermOfPayment {
+        id: String,
+    },
+
+    #[error("Invalid amendment. {msg:?}")]
+    InvalidAmendment {
+        msg: String,
+    },
+
+    #[error("Invalid reporters. {msg:?}")]
+    InvalidReporters {
+        msg: String,
+    },
+
+    #[error("Invalid report. {msg:?}")]
+    InvalidReport {
+        msg: String,
+    },
+
+    #[error("Invalid data provider. {msg:?}")]
+    InvalidDataProvider {
+        msg: String,
+    },
+
+    #[error("Invalid signature")]
+    InvalidSignature {},
+
+    #[error("Nonce already used (nonce {nonce}, last {last})")]
+    InvalidNonce {
+        nonce: u64,
+        last: u64,
+    },
+
+    #[error("Invalid payout. {msg:?} (id {id})")]
+    InvalidPayout {
+        id: u64,
+        msg: String,
+    },
+
+    #[error("No challenge period")]
+    NoChallengePeriod {},
+
+    #[error("Challenge period ended (id {id})")]
+    ChallengePeriodEnded {
+        id: u64,
+    },
+
+    #[error("Challenge period not ended (id {id})")]
+    ChallengePeriodNotEnded {
+        id: u64,
+    },
+
+    #[error("Invalid enrollment. {msg:?}")]
+    InvalidEnrollment {
+        msg: String,
+    },
+
+    #[error("Already enrolled (joiner {joiner:?})")]
+    AlreadyEnrolled {
+        joiner: String,
+    },
+
+    #[error("Not enrolled (joiner {joiner:?})")]
+    NotEnrolled {
+        joiner: String,
+    },
 }
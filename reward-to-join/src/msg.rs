@@ -1,66 +1,335 @@
-use cosmwasm_std::{Addr, Coin};
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use crate::state::Achievement;
+use crate::state::{Achievement, AmendmentRecord, Payout, Report, State};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
-    pub joiner: String,
+    /// If not set, the contract is a program enrolling many joiners with `Enroll`,
+    /// and `reporters`, `data_provider` and `challenge` apply to every enrollment.
+    pub joiner: Option<String>,
     pub term_of_payments: Vec<TermOfPayment>,
-    pub end_time_millis: Option<u64>,
+    pub end_time: Option<Timestamp>,
+    /// Metrics of the achievement. Default is the counter `treatments` and the boolean `insurance_claim`.
+    pub metrics: Option<Vec<MetricSpec>>,
+    /// If set, the metrics of these reporters are reported by them instead of the payer.
+    pub reporters: Option<Vec<Reporter>>,
+    /// Distinct reporters reporting the same value before it is accepted. Default is 1.
+    pub quorum: Option<u32>,
+    /// If set, the joiner can claim the metrics without reporters signed by this data provider.
+    pub data_provider: Option<DataProvider>,
+    /// If set, approved rewards are paid after a challenge period instead of immediately.
+    pub challenge: Option<Challenge>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Challenge {
+    /// Period in which the payer can dispute a payout, greater than 0
+    pub period: Duration,
+    /// Resolves the disputes
+    pub arbiter: String,
+}
+
+/// Trusted key signing the achievement claims of the joiner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DataProvider {
+    pub scheme: SignatureScheme,
+    pub public_key: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    /// Signature of the sha256 hash of the payload by a compressed or uncompressed public key
+    Secp256k1,
+    /// Signature of the payload
+    Ed25519,
+}
+
+/// Payload signed by the data provider, serialized as JSON in the order of the fields
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimPayload {
+    pub contract: String,
+    pub joiner: String,
+    pub metric: String,
+    pub value: MetricValue,
+    pub evidence_hash: String,
+    /// Greater than the nonce of the last claim of the joiner
+    pub nonce: u64,
+}
+
+/// Address authorized to report `metrics`, e.g. a hospital or an insurer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Reporter {
+    pub address: String,
+    pub metrics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    /// Count increased by `Append`
+    Counter,
+    Boolean,
+    /// Last reported value
+    LatestValue,
+}
+
+impl MetricKind {
+    pub fn initial_value(&self) -> MetricValue {
+        match self {
+            MetricKind::Counter => MetricValue::Counter(0),
+            MetricKind::Boolean => MetricValue::Boolean(false),
+            MetricKind::LatestValue => MetricValue::LatestValue(0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MetricSpec {
+    pub name: String,
+    pub kind: MetricKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricValue {
+    Counter(u64),
+    Boolean(bool),
+    LatestValue(u64),
+}
+
+impl MetricValue {
+    pub fn kind(&self) -> MetricKind {
+        match self {
+            MetricValue::Counter(_) => MetricKind::Counter,
+            MetricValue::Boolean(_) => MetricKind::Boolean,
+            MetricValue::LatestValue(_) => MetricKind::LatestValue,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Metric {
+    pub name: String,
+    pub value: MetricValue,
 }
 
+/// Milestone rule of a term, evaluated against the achievement
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct ContractContent {
-    pub treatments: u64,
-    pub insurance_claim: bool,
-    pub period_days: u64,
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    /// The counter or the latest value of `metric` is at least `n`
+    MinCount { metric: String, n: u64 },
+    /// The flag `name` is set
+    Flag { name: String },
+    /// `duration` has elapsed since the start of the contract
+    ElapsedAtLeast { duration: Duration },
 }
 
-impl fmt::Display for ContractContent {
+impl Condition {
+    /// Least seconds from the start of the contract until the condition can be met, or None on overflow
+    pub fn required_seconds(&self) -> Option<u64> {
+        match self {
+            Condition::All(conditions) => conditions.iter().map(|c| c.required_seconds()).collect::<Option<Vec<u64>>>()
+                .map(|seconds| seconds.into_iter().max().unwrap_or(0)),
+            Condition::Any(conditions) => conditions.iter().map(|c| c.required_seconds()).collect::<Option<Vec<u64>>>()
+                .map(|seconds| seconds.into_iter().min().unwrap_or(0)),
+            Condition::ElapsedAtLeast { duration } => duration.seconds(),
+            Condition::Not(_) | Condition::MinCount { .. } | Condition::Flag { .. } => Some(0),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "treatments: {}, insurance_claim: {}, period_days: {}",
-               self.treatments, self.insurance_claim, self.period_days)
+        match self {
+            Condition::All(conditions) => write!(f, "all({})", conditions.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+            Condition::Any(conditions) => write!(f, "any({})", conditions.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(", ")),
+            Condition::Not(condition) => write!(f, "not({})", condition),
+            Condition::MinCount { metric, n } => write!(f, "{} >= {}", metric, n),
+            Condition::Flag { name } => write!(f, "{}", name),
+            Condition::ElapsedAtLeast { duration } => write!(f, "elapsed >= {}", duration),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Duration {
+    Seconds(u64),
+    Days(u64),
+}
+
+impl Duration {
+    /// Seconds of the duration, or None on overflow
+    pub fn seconds(&self) -> Option<u64> {
+        match self {
+            Duration::Seconds(seconds) => Some(*seconds),
+            Duration::Days(days) => days.checked_mul(24 * 60 * 60),
+        }
+    }
 
+    /// Time after the duration from `time`, or None on overflow
+    pub fn after(&self, time: Timestamp) -> Option<Timestamp> {
+        self.seconds()
+            .and_then(|seconds| seconds.checked_mul(1_000_000_000))
+            .and_then(|nanos| time.nanos().checked_add(nanos))
+            .map(Timestamp::from_nanos)
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Duration::Seconds(seconds) => write!(f, "{} seconds", seconds),
+            Duration::Days(days) => write!(f, "{} days", days),
+        }
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct TermOfPayment {
     pub id: String,
-    pub contract_content: ContractContent,
+    pub condition: Condition,
     pub amount: Coin,
     pub is_payment: bool,
 }
 
 impl fmt::Display for TermOfPayment {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "id: {}, contract_content: {}, amount:{}, is_payment:{}",
-               self.id, self.contract_content, self.amount, self.is_payment)
+        write!(f, "id: {}, condition: {}, amount:{}, is_payment:{}",
+               self.id, self.condition, self.amount, self.is_payment)
     }
 }
 
+/// Changes of the unpaid terms agreed by the payer and the joiner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Amendment {
+    pub add: Vec<TermOfPayment>,
+    /// Ids of the terms to remove
+    pub remove: Vec<String>,
+    pub reprice: Vec<Reprice>,
+    /// Later `end_time`
+    pub end_time: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Reprice {
+    pub id: String,
+    pub amount: Coin,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
+    /// Set the values of the metrics. `joiner` selects the enrollment of a program contract.
     Update {
-        treatments: Option<u64>,
-        insurance_claim: Option<bool>,
+        metrics: Vec<Metric>,
+        joiner: Option<String>,
     },
+    /// Add `n` to the counter `metric`
     Append {
-        treatments: Option<u64>,
+        metric: String,
+        n: u64,
+        joiner: Option<String>,
     },
     Approve {},
-    Refund {},
+    /// Once expired, pay the terms achieved before `end_time` to the joiner and refund the rest to the payer
+    Refund {
+        joiner: Option<String>,
+    },
+    /// Report the value of `metric` with the hash of its evidence. Only a registered reporter can report.
+    /// A counter is reported by its total.
+    Report {
+        metric: Metric,
+        evidence_hash: String,
+        joiner: Option<String>,
+    },
+    /// Set the value of `metric` of the sender, signed by the data provider over `ClaimPayload`
+    ClaimAchievement {
+        metric: String,
+        value: MetricValue,
+        evidence_hash: String,
+        nonce: u64,
+        signature: Binary,
+    },
+    /// Dispute the pending payout `id` within the challenge period. Only the payer can dispute.
+    Dispute {
+        id: u64,
+        reason: String,
+        joiner: Option<String>,
+    },
+    /// Pay the disputed payout `id` to the joiner if `approve`, or refund it to the payer. Only the arbiter can resolve.
+    ResolveDispute {
+        id: u64,
+        approve: bool,
+        joiner: Option<String>,
+    },
+    /// Receive the undisputed payout `id` of the sender after the challenge period
+    ClaimPayout {
+        id: u64,
+    },
+    /// Refund the funds exceeding the unpaid terms to the payer before expiry. Only the payer can refund.
+    RefundExcess {
+        joiner: Option<String>,
+    },
+    /// Enroll a joiner in a program contract with the sent funds. Only the payer can enroll.
+    Enroll {
+        joiner: String,
+        term_of_payments: Vec<TermOfPayment>,
+        end_time: Option<Timestamp>,
+    },
+    /// Add the sent funds to the escrow. Only the payer can top up.
+    TopUp {
+        joiner: Option<String>,
+    },
+    /// Propose an amendment to the other party. It replaces the pending proposal.
+    ProposeAmendment {
+        amendment: Amendment,
+        joiner: Option<String>,
+    },
+    /// Accept and apply the pending amendment `id` proposed by the other party
+    AcceptAmendment {
+        id: u64,
+        joiner: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    TermOfPayments {},
-    Achievement {},
+    TermOfPayments {
+        joiner: Option<String>,
+    },
+    Achievement {
+        joiner: Option<String>,
+    },
+    /// Joiner of a single contract. A program contract has no single joiner; query `Enrollments`.
     Joiner {},
+    /// Enrollments of a program contract in the order of joiner address
+    Enrollments {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Pending proposal and accepted amendments
+    Amendments {
+        joiner: Option<String>,
+    },
+    Reporters {},
+    DataProvider {},
+    Challenge {},
+    Payouts {
+        joiner: Option<String>,
+    },
+    /// Reports waiting for the quorum and accepted reports
+    Reports {
+        joiner: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -78,3 +347,41 @@ pub struct JoinerResponse {
     pub joiner: Addr,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AmendmentsResponse {
+    pub pending: Option<AmendmentRecord>,
+    pub amendments: Vec<AmendmentRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReportersResponse {
+    pub reporters: Vec<Reporter>,
+    pub quorum: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DataProviderResponse {
+    pub data_provider: DataProvider,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChallengeResponse {
+    pub period: Duration,
+    pub arbiter: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PayoutsResponse {
+    pub payouts: Vec<Payout>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReportsResponse {
+    pub reports: Vec<Report>,
+    pub accepted_reports: Vec<Report>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EnrollmentsResponse {
+    pub enrollments: Vec<State>,
+}
@@ -1,13 +1,19 @@
 use std::fmt;
 
-use cosmwasm_std::{Addr, Coin, Env, Storage};
-use cosmwasm_storage::{ReadonlySingleton, singleton, Singleton, singleton_read};
+use cosmwasm_std::{Addr, Coin, Env, StdResult, Storage, Timestamp};
+use cosmwasm_storage::{Bucket, bucket, bucket_read, ReadonlyBucket, ReadonlySingleton, singleton, Singleton, singleton_read};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::msg::{ContractContent, TermOfPayment};
+use crate::error::ContractError;
+use crate::msg::{Amendment, Condition, DataProvider, Duration, Metric, MetricKind, MetricSpec, MetricValue, Reporter, TermOfPayment};
 
 static CONFIG_KEY: &[u8] = b"config";
+static PROGRAM_KEY: &[u8] = b"program";
+static ENROLLMENTS_KEY: &[u8] = b"enrollments";
+static REPORTERS_KEY: &[u8] = b"reporters";
+static DATA_PROVIDER_KEY: &[u8] = b"data_provider";
+static CHALLENGE_KEY: &[u8] = b"challenge";
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -17,8 +23,20 @@ pub struct State {
     pub total_amount: Vec<Coin>,
     pub term_of_payments: Vec<TermOfPayment>,
     pub achievement: Achievement,
-    pub start_time_millis: Option<u64>,
-    pub end_time_millis: Option<u64>,
+    pub start_time: Timestamp,
+    pub end_time: Option<Timestamp>,
+    /// Amendment proposed by the payer or the joiner, waiting for the other party
+    pub pending_amendment: Option<AmendmentRecord>,
+    /// Accepted amendments in the order of acceptance
+    pub amendments: Vec<AmendmentRecord>,
+    /// Reports of the registered reporters waiting for the quorum, at most one per reporter and metric
+    pub reports: Vec<Report>,
+    /// Reports that reached the quorum in the order of acceptance
+    pub accepted_reports: Vec<Report>,
+    /// Nonce of the last claim of the joiner
+    pub claim_nonce: u64,
+    /// Approved rewards waiting for the challenge period
+    pub payouts: Vec<Payout>,
 }
 
 impl fmt::Display for State {
@@ -33,15 +51,15 @@ impl fmt::Display for State {
                self.joiner,
                self.total_amount,
                self.term_of_payments,
-               self.start_time_millis.unwrap_or(0),
-               self.end_time_millis.unwrap_or(0))
+               self.start_time,
+               self.end_time.unwrap_or_default())
     }
 }
 
 impl State {
     pub fn is_expired(&self, env: &Env) -> bool {
-        if let Some(end_time_millis) = self.end_time_millis {
-            if env.block.time.nanos() > end_time_millis * 1000000 {
+        if let Some(end_time) = self.end_time {
+            if env.block.time > end_time {
                 return true;
             }
         }
@@ -57,7 +75,7 @@ impl State {
                 continue;
             }
 
-            if achievement.is_achievement(&term_of_payment.contract_content, self.start_time_millis, env) {
+            if achievement.is_achievement(&term_of_payment.condition, self.start_time, env) {
                 amount.push(term_of_payment.amount.clone());
                 term_of_payment.is_payment = true;
             }
@@ -66,56 +84,281 @@ impl State {
         amount
     }
 
-    pub fn append_treatments_in_achievement(&mut self, treatments: Option<u64>) {
-       self.achievement.treatments = self.achievement.treatments + treatments.unwrap();
+    /// Amounts of `total_amount` that are not paid to the joiner yet.
+    pub fn unpaid_amounts(&self) -> Vec<Coin> {
+        let paid: Vec<Coin> = self.term_of_payments.iter()
+            .filter(|t| t.is_payment)
+            .map(|t| t.amount.clone())
+            .collect();
+        subtract_amounts(self.total_amount.clone(), &paid)
     }
 
-    pub fn update_treatments_in_achievement(&mut self, treatments: Option<u64>, insurance_claim: Option<bool>) {
-        if treatments != None {
-            self.achievement.treatments = treatments.unwrap();
+    /// Amounts of the terms not paid yet.
+    pub fn outstanding_amounts(&self) -> StdResult<Vec<Coin>> {
+        let mut amounts: Vec<Coin> = vec![];
+        for term_of_payment in self.term_of_payments.iter().filter(|t| !t.is_payment) {
+            match amounts.iter_mut().find(|c| c.denom == term_of_payment.amount.denom) {
+                Some(amount) => amount.amount = amount.amount.checked_add(term_of_payment.amount.amount)?,
+                None => amounts.push(term_of_payment.amount.clone()),
+            }
         }
-        if insurance_claim != None {
-            self.achievement.insurance_claim = insurance_claim.unwrap();
+        Ok(amounts)
+    }
+
+    /// Unpaid funds not reserved for the outstanding terms.
+    pub fn excess_amounts(&self) -> StdResult<Vec<Coin>> {
+        Ok(subtract_amounts(self.unpaid_amounts(), &self.outstanding_amounts()?))
+    }
+
+    /// Opens a pending payout of `amount` and returns its id.
+    pub fn open_payout(&mut self, amount: Vec<Coin>, env: &Env) -> u64 {
+        let id = self.payouts.len() as u64 + 1;
+        self.payouts.push(Payout {
+            id,
+            amount,
+            approved_at: env.block.time,
+            status: PayoutStatus::Pending,
+            dispute_reason: None,
+        });
+        id
+    }
+
+    /// Amounts of the payouts that are not paid or refunded yet.
+    pub fn reserved_amounts(&self) -> StdResult<Vec<Coin>> {
+        let mut amounts: Vec<Coin> = vec![];
+        for payout in self.payouts.iter().filter(|p| p.status == PayoutStatus::Pending || p.status == PayoutStatus::Disputed) {
+            for coin in &payout.amount {
+                match amounts.iter_mut().find(|c| c.denom == coin.denom) {
+                    Some(amount) => amount.amount = amount.amount.checked_add(coin.amount)?,
+                    None => amounts.push(coin.clone()),
+                }
+            }
         }
+        Ok(amounts)
     }
 }
 
+/// Subtracts `sub` from `amounts` by denom, dropping the zero amounts.
+pub fn subtract_amounts(mut amounts: Vec<Coin>, sub: &[Coin]) -> Vec<Coin> {
+    for coin in sub {
+        if let Some(amount) = amounts.iter_mut().find(|c| c.denom == coin.denom) {
+            amount.amount = amount.amount.saturating_sub(coin.amount);
+        }
+    }
+    amounts.into_iter().filter(|c| !c.amount.is_zero()).collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AmendmentRecord {
+    pub id: u64,
+    pub proposer: Addr,
+    pub amendment: Amendment,
+    pub proposed_at: Timestamp,
+    pub accepted_at: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Report {
+    pub reporter: Addr,
+    pub metric: Metric,
+    pub evidence_hash: String,
+    pub reported_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Payout {
+    pub id: u64,
+    pub amount: Vec<Coin>,
+    pub approved_at: Timestamp,
+    pub status: PayoutStatus,
+    pub dispute_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    /// Claimable by the joiner after the challenge period
+    Pending,
+    /// Waiting for the arbiter
+    Disputed,
+    /// Paid to the joiner
+    Paid,
+    /// Refunded to the payer by the arbiter
+    Rejected,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Achievement {
-    pub treatments: u64,
-    pub insurance_claim: bool,
+    pub metrics: Vec<Metric>,
 }
 
 impl Achievement {
-    pub fn is_achievement(&self, contract_content: &ContractContent, start_time: Option<u64>, env: &Env) -> bool {
-        if contract_content.treatments > self.treatments {
-            return false;
-        } else if contract_content.insurance_claim == true {
-            if self.insurance_claim == false {
-                return false;
+    pub fn new(specs: &[MetricSpec]) -> Result<Self, ContractError> {
+        let mut metrics: Vec<Metric> = vec![];
+        for spec in specs {
+            if metrics.iter().any(|m| m.name == spec.name) {
+                return Err(ContractError::InvalidMetric { name: spec.name.to_string(), msg: "duplicated metric".to_string() });
             }
-        } else if env.block.time.nanos() < start_time.unwrap() + (contract_content.period_days * 24 * 60 * 60 * 1000) {
-            return false;
+            metrics.push(Metric { name: spec.name.to_string(), value: spec.kind.initial_value() });
         }
+        Ok(Achievement { metrics })
+    }
 
-        return true;
+    pub fn is_achievement(&self, condition: &Condition, start_time: Timestamp, env: &Env) -> bool {
+        match condition {
+            Condition::All(conditions) => conditions.iter().all(|c| self.is_achievement(c, start_time, env)),
+            Condition::Any(conditions) => conditions.iter().any(|c| self.is_achievement(c, start_time, env)),
+            Condition::Not(condition) => !self.is_achievement(condition, start_time, env),
+            Condition::MinCount { metric, n } => self.count(metric) >= *n,
+            Condition::Flag { name } => self.flag(name),
+            Condition::ElapsedAtLeast { duration } => duration.after(start_time).map_or(false, |time| env.block.time >= time),
+        }
+    }
+
+    /// Checks that the metrics of `condition` are declared with the kinds it can evaluate,
+    /// and that its durations from `start_time` do not overflow.
+    pub fn validate_condition(&self, condition: &Condition, start_time: Timestamp) -> Result<(), ContractError> {
+        match condition {
+            Condition::All(conditions) | Condition::Any(conditions) => conditions.iter().try_for_each(|c| self.validate_condition(c, start_time)),
+            Condition::Not(condition) => self.validate_condition(condition, start_time),
+            Condition::MinCount { metric, .. } => self.expect_kind(metric, &[MetricKind::Counter, MetricKind::LatestValue]),
+            Condition::Flag { name } => self.expect_kind(name, &[MetricKind::Boolean]),
+            Condition::ElapsedAtLeast { duration } => match duration.after(start_time) {
+                Some(_) => Ok(()),
+                None => Err(ContractError::InvalidDuration { duration: *duration, msg: "overflow".to_string() }),
+            },
+        }
+    }
+
+    pub fn value(&self, name: &str) -> Option<MetricValue> {
+        self.metrics.iter().find(|m| m.name == name).map(|m| m.value)
+    }
+
+    pub fn count(&self, metric: &str) -> u64 {
+        match self.value(metric) {
+            Some(MetricValue::Counter(n)) | Some(MetricValue::LatestValue(n)) => n,
+            _ => 0,
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        matches!(self.value(name), Some(MetricValue::Boolean(true)))
+    }
+
+    pub fn update(&mut self, metrics: Vec<Metric>) -> Result<(), ContractError> {
+        for metric in metrics {
+            self.expect_kind(&metric.name, &[metric.value.kind()])?;
+            if let Some(m) = self.metrics.iter_mut().find(|m| m.name == metric.name) {
+                m.value = metric.value;
+            }
+        }
+        Ok(())
     }
 
-    pub fn update(&mut self, treatments: Option<u64>, insurance_claim: Option<bool>) {
-        if treatments != None {
-            self.treatments = treatments.unwrap()
+    pub fn append(&mut self, name: &str, n: u64) -> Result<(), ContractError> {
+        self.expect_kind(name, &[MetricKind::Counter])?;
+        if let Some(m) = self.metrics.iter_mut().find(|m| m.name == name) {
+            if let MetricValue::Counter(count) = m.value {
+                let count = count.checked_add(n)
+                    .ok_or_else(|| ContractError::InvalidMetric { name: name.to_string(), msg: "overflow".to_string() })?;
+                m.value = MetricValue::Counter(count);
+            }
         }
-        if insurance_claim != None {
-            self.insurance_claim = insurance_claim.unwrap()
+        Ok(())
+    }
+
+    pub fn expect_kind(&self, name: &str, kinds: &[MetricKind]) -> Result<(), ContractError> {
+        match self.value(name) {
+            None => Err(ContractError::InvalidMetric { name: name.to_string(), msg: "undeclared metric".to_string() }),
+            Some(value) if !kinds.contains(&value.kind()) => Err(ContractError::InvalidMetric { name: name.to_string(), msg: format!("unexpected kind {:?}", value.kind()) }),
+            _ => Ok(()),
         }
     }
 }
 
 
+/// A program contract enrolls many joiners, each with its own `State`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Program {
+    pub payer: Addr,
+    /// Metrics of the achievement of each enrollment
+    pub metrics: Vec<MetricSpec>,
+}
+
+/// Reporters of the achievement, replacing the payer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ReporterSet {
+    pub reporters: Vec<Reporter>,
+    pub quorum: u32,
+}
+
+impl ReporterSet {
+    pub fn is_reporter(&self, address: &Addr, metric: &str) -> bool {
+        self.reporters.iter().any(|r| r.address == address.as_str() && r.metrics.iter().any(|m| m == metric))
+    }
+
+    pub fn is_reported(&self, metric: &str) -> bool {
+        self.reporters.iter().any(|r| r.metrics.iter().any(|m| m == metric))
+    }
+}
+
+/// Challenge period of the payouts and the arbiter of their disputes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChallengeWindow {
+    pub period: Duration,
+    pub arbiter: Addr,
+}
+
 pub fn config(storage: &mut dyn Storage) -> Singleton<State> {
     singleton(storage, CONFIG_KEY)
 }
 
 pub fn config_read(storage: &dyn Storage) -> ReadonlySingleton<State> {
     singleton_read(storage, CONFIG_KEY)
+}
+
+pub fn program(storage: &mut dyn Storage) -> Singleton<Program> {
+    singleton(storage, PROGRAM_KEY)
+}
+
+pub fn program_read(storage: &dyn Storage) -> ReadonlySingleton<Program> {
+    singleton_read(storage, PROGRAM_KEY)
+}
+
+pub fn enrollments(storage: &mut dyn Storage) -> Bucket<State> {
+    bucket(storage, ENROLLMENTS_KEY)
+}
+
+pub fn enrollments_read(storage: &dyn Storage) -> ReadonlyBucket<State> {
+    bucket_read(storage, ENROLLMENTS_KEY)
+}
+
+pub fn reporter_set(storage: &mut dyn Storage) -> Singleton<ReporterSet> {
+    singleton(storage, REPORTERS_KEY)
+}
+
+pub fn reporter_set_read(storage: &dyn Storage) -> ReadonlySingleton<ReporterSet> {
+    singleton_read(storage, REPORTERS_KEY)
+}
+
+pub fn data_provider(storage: &mut dyn Storage) -> Singleton<DataProvider> {
+    singleton(storage, DATA_PROVIDER_KEY)
+}
+
+pub fn data_provider_read(storage: &dyn Storage) -> ReadonlySingleton<DataProvider> {
+    singleton_read(storage, DATA_PROVIDER_KEY)
+}
+
+pub fn challenge_window(storage: &mut dyn Storage) -> Singleton<ChallengeWindow> {
+    singleton(storage, CHALLENGE_KEY)
+}
+
+pub fn challenge_window_read(storage: &dyn Storage) -> ReadonlySingleton<ChallengeWindow> {
+    singleton_read(storage, CHALLENGE_KEY)
 }
\ No newline at end of file